@@ -57,47 +57,72 @@
 //!
 //! How many passwords are valid according to the new interpretation of the policies?
 
+use std::fmt;
+
 pub fn solve() -> crate::Result<()> {
     let input = crate::read_input("day02.txt")?;
-    println!("Day02 part1: {}", solve_part1(&input));
-    println!("Day02 part2: {}", solve_part2(&input));
+    println!("Day02 part1: {}", solve_part1(&input)?);
+    println!("Day02 part2: {}", solve_part2(&input)?);
 
     Ok(())
 }
 
-fn solve_part1(s: &str) -> usize {
-    parse(s)
+fn solve_part1(s: &str) -> Result<usize, ParseError> {
+    Ok(parse(s)?
         .into_iter()
         .filter(|(pol, pw)| {
             let count = pw.chars().filter(|&c| c == pol.c).count();
             pol.min <= count && count <= pol.max
         })
-        .count()
+        .count())
 }
 
-fn solve_part2(s: &str) -> usize {
-    parse(s)
+fn solve_part2(s: &str) -> Result<usize, ParseError> {
+    Ok(parse(s)?
         .into_iter()
-        .filter(|(pol, pw)| {
-            let count = pw
-                .chars()
-                .enumerate()
-                .map(|(i, c)| (i + 1, c))
-                .filter(|&(_, c)| c == pol.c)
-                .filter(|&(i, _)| i == pol.min || i == pol.max)
-                .count();
-            count == 1
-        })
-        .count()
+        .filter(|(pol, pw)| pol.has_char_at(pw, pol.min) != pol.has_char_at(pw, pol.max))
+        .count())
 }
 
-fn parse(s: &str) -> impl IntoIterator<Item = (Policy, &'_ str)> {
-    s.trim().lines().map(|s| s.trim()).map(|s| {
-        let mut split: std::str::Split<'_, _> = s.split(':');
-        let policy = split.next().map(Policy::parse).expect("policy part");
-        let password = split.next().map(|s| s.trim()).expect("Password part");
-        (policy, password)
-    })
+/// Parses every non empty line of `s` into a policy and its password.
+/// Stops at the first malformed line.
+fn parse(s: &str) -> Result<Vec<(Policy, &'_ str)>, ParseError> {
+    s.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_line(line).map_err(|err| err.at_line(i + 1)))
+        .collect()
+}
+
+/// Parses a single line on the form `<min>-<max> <char>: <password>`.
+fn parse_line(s: &str) -> Result<(Policy, &'_ str), ParseError> {
+    let mut cursor = Cursor::new(s);
+    cursor.skip_whitespace();
+
+    let min = cursor.number()?;
+    cursor.expect('-')?;
+    let max_col = cursor.col;
+    let max = cursor.number()?;
+    cursor.expect(' ')?;
+    let c = cursor
+        .next()
+        .ok_or_else(|| cursor.error("expected policy char, found end of line"))?;
+    cursor.expect(':')?;
+    cursor.skip_whitespace();
+
+    let password = cursor.rest().trim_end();
+    if password.is_empty() {
+        return Err(cursor.error("expected password, found end of line"));
+    }
+
+    if max < min {
+        return Err(ParseError::new(
+            max_col,
+            format!("policy max `{}` is less than min `{}`", max, min),
+        ));
+    }
+
+    Ok((Policy { min, max, c }, password))
 }
 
 struct Policy {
@@ -107,21 +132,119 @@ struct Policy {
 }
 
 impl Policy {
-    fn parse(s: &str) -> Self {
-        let mut split = s.split(' ');
-        let minmax = split.next().expect("policy minmax");
-        let mut minmax_split = minmax
-            .split('-')
-            .map(|s| s.parse::<usize>().expect("policy num"));
+    /// Returns true if the 1-indexed `pos` of `pw` holds the policy char.
+    /// Position 0 and positions past the end of `pw` never do.
+    fn has_char_at(&self, pw: &str, pos: usize) -> bool {
+        pos.checked_sub(1)
+            .and_then(|i| pw.chars().nth(i))
+            .map(|c| c == self.c)
+            .unwrap_or(false)
+    }
+}
 
-        let min = minmax_split.next().expect("policy min");
-        let max = minmax_split.next().expect("policy max");
-        let c = s.chars().last().expect("policy char");
+/// Walks a line char by char, keeping track of the 1-indexed column.
+struct Cursor<'a> {
+    s: &'a str,
+    col: usize,
+}
 
-        Self { min, max, c }
+impl<'a> Cursor<'a> {
+    fn new(s: &'a str) -> Self {
+        Self { s, col: 1 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s.chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.s = &self.s[c.len_utf8()..];
+        self.col += 1;
+        Some(c)
+    }
+
+    fn rest(&self) -> &'a str {
+        self.s
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(char::is_whitespace).unwrap_or(false) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.next();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(format!("expected `{}`, found end of line", expected))),
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, ParseError> {
+        let start = self.col;
+        let len = self
+            .s
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.s.len());
+
+        if len == 0 {
+            return Err(match self.peek() {
+                Some(c) => self.error(format!("expected number, found `{}`", c)),
+                None => self.error("expected number, found end of line"),
+            });
+        }
+
+        let (digits, rest) = self.s.split_at(len);
+        let n = digits
+            .parse::<usize>()
+            .map_err(|_| ParseError::new(start, format!("number `{}` is too large", digits)))?;
+
+        self.s = rest;
+        self.col += len;
+        Ok(n)
+    }
+
+    fn error(&self, msg: impl Into<String>) -> ParseError {
+        ParseError::new(self.col, msg)
+    }
+}
+
+/// A malformed line in the password database.
+/// `line` and `col` are both 1-indexed, `line` is 0 until known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    col: usize,
+    msg: String,
+}
+
+impl ParseError {
+    fn new(col: usize, msg: impl Into<String>) -> Self {
+        Self {
+            line: 0,
+            col,
+            msg: msg.into(),
+        }
+    }
+
+    fn at_line(self, line: usize) -> Self {
+        Self { line, ..self }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.col, self.msg)
     }
 }
 
+impl std::error::Error for ParseError {}
+
 #[cfg(test)]
 mod tests {
 
@@ -135,11 +258,43 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(solve_part1(EXAMPLE_INPUT), 2);
+        assert_eq!(solve_part1(EXAMPLE_INPUT), Ok(2));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(solve_part2(EXAMPLE_INPUT), 1);
+        assert_eq!(solve_part2(EXAMPLE_INPUT), Ok(1));
+    }
+
+    #[test]
+    fn parse_unicode_and_long_counts() {
+        let (pol, pw) = parse_line("10-120 é: aéb").expect("parsing line");
+        assert_eq!((pol.min, pol.max, pol.c, pw), (10, 120, 'é', "aéb"));
+
+        let (pol, pw) = parse_line("2-3 🎄: 🎄🎄x").expect("parsing line");
+        assert!(pol.has_char_at(pw, 2));
+        assert!(!pol.has_char_at(pw, 3));
+    }
+
+    #[test]
+    fn positions_out_of_range_never_match() {
+        let (pol, pw) = parse_line("1-9 a: abc").expect("parsing line");
+        assert!(!pol.has_char_at(pw, 0));
+        assert!(!pol.has_char_at(pw, 9));
+        assert_eq!(solve_part2("1-9 a: abc"), Ok(1));
+    }
+
+    #[test]
+    fn parse_errors_report_column() {
+        let col = |s| parse_line(s).err().map(|err| err.col);
+        assert_eq!(col("x-3 a: abc"), Some(1));
+        assert_eq!(col("1-3a: abc"), Some(4));
+        assert_eq!(col("1-3 a abc"), Some(6));
+        assert_eq!(col("1-3 a:"), Some(7));
+        assert_eq!(col("4-3 a: abc"), Some(3));
+        assert_eq!(col("1-99999999999999999999999 a: abc"), Some(3));
+
+        let err = parse("1-3 a: abc\n\n1-3 b cdefg").err().expect("error");
+        assert_eq!((err.line, err.col), (3, 6));
     }
 }
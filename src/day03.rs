@@ -91,48 +91,419 @@
 //! What do you get if you multiply together the number of trees encountered
 //! on each of the listed slopes?
 
+use std::{fmt, str::FromStr};
+
+/// The slopes checked in part 2, as `(right, down)`.
+const PART2_SLOPES: [(i64, usize); 5] = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
+
 pub fn solve() -> crate::Result<()> {
-    let input = crate::read_input("day03.txt")?;
-    println!("Day03 part1: {}", solve_part1(&input));
-    println!("Day03 part2: {}", solve_part2(&input));
+    let forest = crate::read_input("day03.txt")?.parse::<Forest>()?;
+
+    match std::env::args().nth(2).as_deref() {
+        Some("search") => search::run(&forest, std::env::args().skip(3))?,
+        Some("render") => render::run(&forest, std::env::args().skip(3))?,
+        _ => {
+            println!("Day03 part1: {}", solve_part1(&forest));
+            println!("Day03 part2: {}", solve_part2(&forest));
+        }
+    }
 
     Ok(())
 }
+
 /// Returns the number of trees encountered.
-fn solve_part1(input: &str) -> usize {
-    solver(input, 3, 1)
+fn solve_part1(forest: &Forest) -> usize {
+    forest.trees_on(Slope::new(3, 1).expect("valid slope"))
 }
 
-/// Returns the number of trees encountered.
-fn solve_part2(input: &str) -> usize {
-    solver(input, 1, 1)
-        * solver(input, 3, 1)
-        * solver(input, 5, 1)
-        * solver(input, 7, 1)
-        * solver(input, 1, 2)
+/// Returns the product of the trees encountered on each of the part 2 slopes.
+fn solve_part2(forest: &Forest) -> usize {
+    forest
+        .trees_on_slopes(&part2_slopes())
+        .into_iter()
+        .product()
 }
 
-/// Returns the number of trees encountered.
-///
-/// # Arguments
+fn part2_slopes() -> Vec<Slope> {
+    PART2_SLOPES
+        .iter()
+        .map(|&(dx, dy)| Slope::new(dx, dy).expect("valid slope"))
+        .collect()
+}
+
+/// A movement of `dx` steps right (negative is left) and `dy` rows down
+/// per iteration. `dy` is always at least 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Slope {
+    dx: i64,
+    dy: usize,
+}
+
+impl Slope {
+    fn new(dx: i64, dy: usize) -> Result<Self, String> {
+        if dy == 0 {
+            return Err(format!(
+                "Slope right {} down 0 never leaves the first row",
+                dx
+            ));
+        }
+
+        Ok(Self { dx, dy })
+    }
+
+    /// Returns the column visited on `row` within a pattern `width` wide,
+    /// or `None` if the slope skips that row.
+    /// `dx` is wrapped first, so the column cannot overflow for any `dx`.
+    fn col_at(&self, row: usize, width: usize) -> Option<usize> {
+        if row % self.dy != 0 {
+            return None;
+        }
+
+        let width = width as i64;
+        let step = self.dx.rem_euclid(width);
+        Some(((row / self.dy) as i64 * step).rem_euclid(width) as usize)
+    }
+
+    /// Returns the unwrapped column visited on `row`, or `None` if the
+    /// slope skips that row or the column does not fit in an `i64`.
+    fn unwrapped_col_at(&self, row: usize) -> Option<i64> {
+        if row % self.dy != 0 {
+            return None;
+        }
+
+        ((row / self.dy) as i64).checked_mul(self.dx)
+    }
+}
+
+impl fmt::Display for Slope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "right {}, down {}", self.dx, self.dy)
+    }
+}
+
+/// The map of the area, a pattern repeating infinitely to the right (and left).
+struct Forest {
+    trees: Vec<bool>,
+    width: usize,
+    height: usize,
+}
+
+impl Forest {
+    /// Returns true if there is a tree at `col` on `row`.
+    /// `col` wraps around the pattern in both directions,
+    /// rows outside of the map never have trees.
+    fn is_tree(&self, col: i64, row: usize) -> bool {
+        if self.height <= row {
+            return false;
+        }
+
+        let col = col.rem_euclid(self.width as i64) as usize;
+        self.trees[row * self.width + col]
+    }
+
+    /// Returns the number of trees encountered going down `slope`
+    /// from the top left corner.
+    fn trees_on(&self, slope: Slope) -> usize {
+        self.trees_on_slopes(&[slope])[0]
+    }
+
+    /// Returns the number of trees encountered for each of the `slopes`,
+    /// in the same order, visiting every row only once.
+    fn trees_on_slopes(&self, slopes: &[Slope]) -> Vec<usize> {
+        let mut counts = vec![0; slopes.len()];
+
+        for row in 0..self.height {
+            for (count, slope) in counts.iter_mut().zip(slopes) {
+                if let Some(col) = slope.col_at(row, self.width) {
+                    if self.is_tree(col as i64, row) {
+                        *count += 1;
+                    }
+                }
+            }
+        }
+
+        counts
+    }
+}
+
+impl FromStr for Forest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut trees = Vec::new();
+        let mut width = None;
+        let mut height = 0;
+
+        for (row, line) in s.trim().lines().map(|s| s.trim()).enumerate() {
+            match width {
+                None => width = Some(line.len()),
+                Some(w) if w != line.len() => {
+                    return Err(format!(
+                        "Row {} is {} wide, expected {}",
+                        row + 1,
+                        line.len(),
+                        w
+                    ))
+                }
+                _ => (),
+            }
+
+            for (col, c) in line.chars().enumerate() {
+                match c {
+                    '#' => trees.push(true),
+                    '.' => trees.push(false),
+                    c => {
+                        return Err(format!(
+                            "Unknown square `{}` at row {}, col {}",
+                            c,
+                            row + 1,
+                            col + 1
+                        ))
+                    }
+                }
+            }
+
+            height += 1;
+        }
+
+        match width {
+            Some(width) if width > 0 => Ok(Self {
+                trees,
+                width,
+                height,
+            }),
+            _ => Err("Empty forest map".to_string()),
+        }
+    }
+}
+
+/// Brute force search for the slopes hitting the fewest and most trees.
 ///
-/// * `input` - The input string
-/// * `x_step` - The steps to move on each line per iteration.
-/// * `y_step` - The number of lines to jump after each iteration.
+/// Run with `cargo run 3 search [dx=MIN..MAX] [dy=MIN..MAX] [csv]`,
+/// both ranges are inclusive.
+mod search {
+    use super::{Forest, Slope};
+    use std::{fmt::Write, ops::RangeInclusive};
+
+    /// The slopes to search, `dy` must not include 0.
+    pub struct Bounds {
+        pub dx: RangeInclusive<i64>,
+        pub dy: RangeInclusive<usize>,
+    }
+
+    impl Default for Bounds {
+        fn default() -> Self {
+            Self {
+                dx: -10..=10,
+                dy: 1..=5,
+            }
+        }
+    }
+
+    pub fn run(forest: &Forest, args: impl Iterator<Item = String>) -> crate::Result<()> {
+        let mut bounds = Bounds::default();
+        let mut csv = false;
+
+        for arg in args {
+            match arg.split_once('=') {
+                Some(("dx", range)) => bounds.dx = parse_range(range)?,
+                Some(("dy", range)) => bounds.dy = parse_range(range)?,
+                None if arg == "csv" => csv = true,
+                _ => return Err(format!("Unknown search argument `{}`", arg).into()),
+            }
+        }
+
+        let ranking = rank(forest, &bounds)?;
+
+        if csv {
+            print!("{}", to_csv(&ranking));
+        } else {
+            print!("{}", comparison(forest, &ranking));
+        }
+
+        Ok(())
+    }
+
+    /// Returns every slope within `bounds` and the trees hit on it,
+    /// ordered from the fewest to the most trees.
+    /// Ties are ordered by `dy` and then `dx`.
+    pub fn rank(forest: &Forest, bounds: &Bounds) -> crate::Result<Vec<(Slope, usize)>> {
+        let slopes = bounds
+            .dy
+            .clone()
+            .flat_map(|dy| bounds.dx.clone().map(move |dx| Slope::new(dx, dy)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if slopes.is_empty() {
+            return Err("No slopes within the search bounds".into());
+        }
+
+        let mut ranking = slopes
+            .iter()
+            .copied()
+            .zip(forest.trees_on_slopes(&slopes))
+            .collect::<Vec<_>>();
+
+        ranking.sort_by_key(|&(slope, trees)| (trees, slope.dy, slope.dx));
+        Ok(ranking)
+    }
+
+    /// Returns the rank of every slope in the sorted `ranking`.
+    /// Slopes hitting equally many trees share the same rank.
+    fn ranks(ranking: &[(Slope, usize)]) -> Vec<usize> {
+        let mut ranks = Vec::with_capacity(ranking.len());
+        let mut rank = 1;
+        for (i, &(_, trees)) in ranking.iter().enumerate() {
+            if i > 0 && ranking[i - 1].1 != trees {
+                rank = i + 1;
+            }
+            ranks.push(rank);
+        }
+        ranks
+    }
+
+    /// Returns the rank a slope hitting `trees` trees would get in the sorted `ranking`.
+    fn rank_of(ranking: &[(Slope, usize)], trees: usize) -> usize {
+        ranking.partition_point(|&(_, t)| t < trees) + 1
+    }
+
+    /// Formats the ranking as CSV with a `rank,dx,dy,trees` header.
+    pub fn to_csv(ranking: &[(Slope, usize)]) -> String {
+        let mut out = String::from("rank,dx,dy,trees\n");
+        for (&(slope, trees), rank) in ranking.iter().zip(ranks(ranking)) {
+            writeln!(out, "{},{},{},{}", rank, slope.dx, slope.dy, trees).expect("writing csv");
+        }
+        out
+    }
+
+    /// Formats the best and worst slopes of the ranking side by side with
+    /// the canonical part 2 slopes, which need not be within the search bounds.
+    pub fn comparison(forest: &Forest, ranking: &[(Slope, usize)]) -> String {
+        let canonical = super::part2_slopes();
+        let canonical_trees = forest.trees_on_slopes(&canonical);
+
+        let ranks = ranks(ranking);
+        let mut rows = Vec::new();
+        if let (Some(&(slope, trees)), Some(&rank)) = (ranking.first(), ranks.first()) {
+            rows.push(("best", slope, trees, rank));
+        }
+        if let (Some(&(slope, trees)), Some(&rank)) = (ranking.last(), ranks.last()) {
+            rows.push(("worst", slope, trees, rank));
+        }
+        rows.extend(
+            canonical
+                .into_iter()
+                .zip(canonical_trees)
+                .map(|(slope, trees)| ("part 2", slope, trees, rank_of(ranking, trees))),
+        );
+
+        let mut out = format!("{:<7} {:<20} {:>6} {:>6}\n", "", "slope", "trees", "rank");
+        for (label, slope, trees, rank) in rows {
+            writeln!(
+                out,
+                "{:<7} {:<20} {:>6} {:>6}",
+                label,
+                slope.to_string(),
+                trees,
+                rank
+            )
+            .expect("writing table");
+        }
+        out.push_str(&format!("{} slopes searched\n", ranking.len()));
+
+        out
+    }
+
+    /// Parses an inclusive range on the form `MIN..MAX` or `MIN..=MAX`.
+    fn parse_range<T: std::str::FromStr>(s: &str) -> crate::Result<RangeInclusive<T>> {
+        let (min, max) = s
+            .split_once("..")
+            .ok_or_else(|| format!("Invalid range `{}`, expected MIN..MAX", s))?;
+        let max = max.trim_start_matches('=');
+
+        let parse = |n: &str| {
+            n.parse::<T>()
+                .map_err(|_| format!("Invalid number `{}` in range `{}`", n, s))
+        };
+
+        Ok(parse(min)?..=parse(max)?)
+    }
+}
+
+/// Draws the path of a slope over the map the way the puzzle text does,
+/// `O` where an open square was hit and `X` where a tree was hit.
 ///
-fn solver(input: &str, x_step: usize, y_step: usize) -> usize {
-    let mut tree_count = 0;
-    let mut col_index = 0;
+/// Run with `cargo run 3 render [dx=N] [dy=N] [plain]`, defaults to
+/// right 3, down 1 with ANSI colours.
+mod render {
+    use super::{Forest, Slope};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Style {
+        Plain,
+        Ansi,
+    }
+
+    pub fn run(forest: &Forest, args: impl Iterator<Item = String>) -> crate::Result<()> {
+        let (mut dx, mut dy) = (3, 1);
+        let mut style = Style::Ansi;
 
-    for line in input.trim().lines().map(|s| s.trim()).step_by(y_step) {
-        let c = line.chars().nth(col_index % line.len()).unwrap();
-        if c == '#' {
-            tree_count += 1;
+        for arg in args {
+            match arg.split_once('=') {
+                Some(("dx", n)) => dx = n.parse().map_err(|_| format!("Invalid dx `{}`", n))?,
+                Some(("dy", n)) => dy = n.parse().map_err(|_| format!("Invalid dy `{}`", n))?,
+                None if arg == "plain" => style = Style::Plain,
+                _ => return Err(format!("Unknown render argument `{}`", arg).into()),
+            }
         }
-        col_index += x_step;
+
+        print!("{}", render(forest, Slope::new(dx, dy)?, style)?);
+        Ok(())
     }
 
-    tree_count
+    /// Renders the map with the path of `slope` drawn on it.
+    ///
+    /// The pattern is repeated as many whole times as the path needs,
+    /// to the left as well for negative slopes, so the original map
+    /// always starts at a multiple of its width.
+    /// The starting square is left unmarked, as in the puzzle text.
+    /// Fails if the path goes further than an `i64` column.
+    pub fn render(forest: &Forest, slope: Slope, style: Style) -> Result<String, String> {
+        let width = forest.width as i64;
+        let path = (0..forest.height)
+            .map(|row| match slope.unwrapped_col_at(row) {
+                None if row % slope.dy == 0 => {
+                    Err(format!("Slope {} is too wide to render", slope))
+                }
+                col => Ok(col.filter(|_| row != 0)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let cols = path.iter().flatten();
+        let first = cols.clone().min().copied().unwrap_or(0).min(0);
+        let last = cols.max().copied().unwrap_or(0).max(width - 1);
+        let first = first.div_euclid(width) * width;
+        let last = (last.div_euclid(width) + 1) * width;
+
+        let mut out = String::new();
+        for (row, hit) in path.into_iter().enumerate() {
+            for col in first..last {
+                let s = match (hit == Some(col), forest.is_tree(col, row), style) {
+                    (false, false, _) => ".",
+                    (false, true, Style::Plain) => "#",
+                    (false, true, Style::Ansi) => "\x1b[32m#\x1b[0m",
+                    (true, false, Style::Plain) => "O",
+                    (true, false, Style::Ansi) => "\x1b[1;32mO\x1b[0m",
+                    (true, true, Style::Plain) => "X",
+                    (true, true, Style::Ansi) => "\x1b[1;31mX\x1b[0m",
+                };
+                out.push_str(s);
+            }
+            out.push('\n');
+        }
+
+        Ok(out)
+    }
 }
 
 #[cfg(test)]
@@ -153,25 +524,130 @@ mod tests {
 #...##....#
 .#..#...#.#"#;
 
+    fn example() -> Forest {
+        EXAMPLE_INPUT.parse().expect("Parsing forest")
+    }
+
+    fn input() -> Forest {
+        crate::read_input("day03.txt")
+            .expect("Reading input")
+            .parse()
+            .expect("Parsing forest")
+    }
+
     #[test]
     fn part1_example() {
-        assert_eq!(solve_part1(EXAMPLE_INPUT.trim()), 7);
+        assert_eq!(solve_part1(&example()), 7);
     }
 
     #[test]
     fn part1() {
-        let input = crate::read_input("day03.txt").expect("Reading input");
-        assert_eq!(solve_part1(&input), 184);
+        assert_eq!(solve_part1(&input()), 184);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(solve_part2(EXAMPLE_INPUT.trim()), 336);
+        assert_eq!(solve_part2(&example()), 336);
     }
 
     #[test]
     fn part2() {
-        let input = crate::read_input("day03.txt").expect("Reading input");
-        assert_eq!(solve_part2(&input), 2431272960);
+        assert_eq!(solve_part2(&input()), 2431272960);
+    }
+
+    #[test]
+    fn batch_matches_single_slopes() {
+        let forest = example();
+        let slopes = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2), (-3, 1), (2, 3)]
+            .iter()
+            .map(|&(dx, dy)| Slope::new(dx, dy).expect("valid slope"))
+            .collect::<Vec<_>>();
+
+        let single = slopes
+            .iter()
+            .map(|&slope| forest.trees_on(slope))
+            .collect::<Vec<_>>();
+
+        assert_eq!(forest.trees_on_slopes(&slopes), single);
+        assert_eq!(&single[..5], &[2, 7, 3, 4, 2]);
+    }
+
+    #[test]
+    fn negative_dx_wraps_left() {
+        let forest: Forest = "#..\n..#\n.#.".parse().expect("Parsing forest");
+        assert!(forest.is_tree(-1, 1));
+        assert!(forest.is_tree(-4, 1));
+        assert_eq!(forest.trees_on(Slope::new(-1, 1).expect("valid slope")), 3);
+        assert!(Slope::new(1, 0).is_err());
+
+        // Both wrap to a step of 1 on a map 3 wide, without overflowing.
+        for dx in [i64::MAX, i64::MIN] {
+            let slope = Slope::new(dx, 1).expect("valid slope");
+            assert_eq!(forest.trees_on(slope), 1);
+            assert!(render::render(&forest, slope, render::Style::Plain).is_err());
+        }
+    }
+
+    #[test]
+    fn search_example() {
+        let forest = example();
+        let bounds = search::Bounds {
+            dx: -7..=7,
+            dy: 1..=2,
+        };
+        let ranking = search::rank(&forest, &bounds).expect("ranking slopes");
+
+        assert_eq!(ranking.len(), 30);
+        assert!(ranking.windows(2).all(|w| w[0].1 <= w[1].1));
+        for slope in part2_slopes() {
+            assert!(ranking.contains(&(slope, forest.trees_on(slope))));
+        }
+
+        let csv = search::to_csv(&ranking);
+        assert_eq!(csv.lines().count(), 31);
+        assert!(csv.starts_with("rank,dx,dy,trees\n1,"));
+
+        let table = search::comparison(&forest, &ranking);
+        assert!(table.contains("right 3, down 1"));
+        assert!(table.contains("30 slopes searched"));
+
+        let flat = search::Bounds {
+            dx: 1..=1,
+            dy: 0..=1,
+        };
+        assert!(search::rank(&forest, &flat).is_err());
+    }
+
+    #[test]
+    fn render_example() {
+        // The first three repetitions of the map in the puzzle text.
+        let expected = r#"
+..##.........##.........##.......
+#..O#...#..#...#...#..#...#...#..
+.#....X..#..#....#..#..#....#..#.
+..#.#...#O#..#.#...#.#..#.#...#.#
+.#...##..#..X...##..#..#...##..#.
+..#.##.......#.X#.......#.##.....
+.#.#.#....#.#.#.#.O..#.#.#.#....#
+.#........#.#........X.#........#
+#.##...#...#.##...#...#.X#...#...
+#...##....##...##....##...#X....#
+.#..#...#.#.#..#...#.#.#..#...X.#
+"#;
+        let slope = Slope::new(3, 1).expect("valid slope");
+        let rendered = render::render(&example(), slope, render::Style::Plain).expect("rendering");
+        assert_eq!(rendered, expected.trim_start());
+
+        let ansi = render::render(&example(), slope, render::Style::Ansi).expect("rendering");
+        assert_eq!(ansi.matches("1;31mX").count(), 7);
+        assert_eq!(ansi.matches("1;32mO").count(), 3);
+    }
+
+    #[test]
+    fn render_negative_slope_expands_left() {
+        let forest: Forest = "#..\n..#\n.#.".parse().expect("Parsing forest");
+        let slope = Slope::new(-2, 1).expect("valid slope");
+        let rendered = render::render(&forest, slope, render::Style::Plain).expect("rendering");
+        assert_eq!(rendered, "#..#..#..\n..#.O#..#\n.#O.#..#.\n");
     }
 }
@@ -134,40 +134,61 @@
 //! Count the number of valid passports - those that have all required fields and valid values.
 //! Continue to treat cid as optional. In your batch file, how many passports are valid?
 
-use std::{fmt, str::FromStr};
+use std::fmt;
 
 pub fn solve() -> crate::Result<()> {
+    match std::env::args().nth(2).as_deref() {
+        Some("check") => return check(std::env::args().skip(3)),
+        Some("export") => return export(std::env::args().nth(3).as_deref()),
+        _ => (),
+    }
+
     let input = crate::read_input("day04.txt")?;
-    println!("Day04 part1: {}", part1(&input));
-    println!("Day04 part2: {}", part2(&input));
+    println!("Day04 part1: {}", part1(&input)?);
+    println!("Day04 part2: {}", part2(&input)?);
     Ok(())
 }
 
-fn part1(s: &str) -> usize {
-    parse(s)
-        .into_iter()
-        .filter(|p| p.meets_p1_standards())
-        .count()
+fn part1(s: &str) -> Result<usize, reader::ReadError> {
+    let schema = schema::Schema::part1();
+    Ok(parse(s)?.into_iter().filter(|r| schema.is_valid(r)).count())
 }
 
-fn part2(s: &str) -> usize {
-    let (valid, invalid): (Vec<_>, Vec<_>) =
-        parse(s).into_iter().partition(|p| p.meets_p2_standards());
+fn part2(s: &str) -> Result<usize, reader::ReadError> {
+    let schema = schema::Schema::part2();
+    let (valid, invalid): (Vec<_>, Vec<_>) = parse(s)?
+        .into_iter()
+        .map(|r| {
+            let violations = schema.validate(&r);
+            (r, violations)
+        })
+        .partition(|(_, violations)| violations.is_empty());
 
     let arg = std::env::args().nth(2);
 
     match arg.as_deref() {
         Some("invalid") => {
-            invalid.iter().for_each(|pp| {
-                println!("{}", pp);
+            invalid.iter().for_each(|(pp, violations)| {
+                println!("{}", pp.describe());
+                for v in violations {
+                    println!("    {}", v);
+                }
             });
 
             println!("Total (invalid): {}", invalid.len());
         }
 
+        Some("report") => {
+            let violations = invalid.iter().flat_map(|(_, v)| v);
+            for (reason, count) in schema::histogram(violations) {
+                println!("{:6} {}", count, reason);
+            }
+            println!("Total (invalid): {}", invalid.len());
+        }
+
         Some("valid") => {
-            valid.iter().for_each(|pp| {
-                println!("{}", pp);
+            valid.iter().for_each(|(pp, _)| {
+                println!("{}", pp.describe());
             });
             println!("Total (valid): {}", valid.len());
         }
@@ -175,175 +196,1187 @@ fn part2(s: &str) -> usize {
         _ => (),
     }
 
-    valid.len()
+    Ok(valid.len())
 }
 
-fn parse(s: &str) -> impl IntoIterator<Item = Passport> {
-    s.trim().split("\n\n").map(|seg| {
-        let mut p = Passport::default();
-        seg.split('\n')
-            .map(|s| s.trim().split(' '))
-            .flatten()
-            .for_each(|pair| {
-                let mut split = pair.trim().split(':');
-                let key = split.next().expect("Reading key");
-                let val = split
-                    .next()
-                    .ok_or_else(|| println!("Reading val from `{}`", pair))
-                    .expect("Reading val");
-
-                match key {
-                    "byr" => p.byr = Some(val),
-                    "iyr" => p.iyr = Some(val),
-                    "eyr" => p.eyr = Some(val),
-                    "hgt" => p.hgt = Some(val),
-                    "hcl" => p.hcl = Some(val),
-                    "ecl" => p.ecl = Some(val),
-                    "pid" => p.pid = Some(val),
-                    "cid" => p.cid = Some(val),
-                    s => panic!("Invalid key `{}`", s),
-                }
-            });
+/// Validates a batch file against a schema loaded from a file.
+///
+/// Run with `cargo run 4 check <schema file> [batch file] [unknown=error|warn|keep]`,
+/// the batch file defaults to the day 4 input and unknown keys are kept.
+/// The batch file is streamed, so it does not have to fit in memory.
+fn check(mut args: impl Iterator<Item = String>) -> crate::Result<()> {
+    use reader::{ReadError, RecordReader, UnknownKeys};
+    use std::{collections::HashMap, fs::File, io::BufReader};
+
+    let schema_path = args.next().ok_or("Missing schema file argument")?;
+    let schema = std::fs::read_to_string(&schema_path)?
+        .parse::<schema::Schema>()
+        .map_err(|err| format!("{}: {}", schema_path, err))?;
+
+    let mut batch_path = "inputs/day04.txt".to_string();
+    let mut policy = UnknownKeys::Keep;
+    for arg in args {
+        policy = match arg.strip_prefix("unknown=") {
+            Some("error") => UnknownKeys::Error,
+            Some("warn") => UnknownKeys::Warn,
+            Some("keep") => UnknownKeys::Keep,
+            Some(p) => return Err(format!("Unknown policy `{}`", p).into()),
+            None => {
+                batch_path = arg;
+                continue;
+            }
+        };
+    }
+
+    let records = RecordReader::new(BufReader::new(File::open(&batch_path)?))
+        .with_known_keys(schema.fields.iter().map(|f| f.name.clone()), policy);
+
+    let mut total = 0;
+    let mut valid = 0;
+    let mut counts = HashMap::new();
+    for record in records {
+        let record = match record {
+            Ok(record) => record,
+            Err(ReadError::Io(err)) => return Err(format!("{}: {}", batch_path, err).into()),
+            Err(err) => {
+                total += 1;
+                println!("{}: {}", batch_path, err);
+                continue;
+            }
+        };
+        total += 1;
+
+        let violations = schema.validate(&record);
+        if violations.is_empty() {
+            valid += 1;
+        }
+        schema::tally(&mut counts, &violations);
+    }
+
+    println!("{}: {} of {} records valid", schema.name, valid, total);
+    for (reason, count) in schema::ranked(counts) {
+        println!("{:6} {}", count, reason);
+    }
 
-        p
-    })
+    Ok(())
 }
 
-impl fmt::Display for Passport<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "byr:{byr:6} iyr:{iyr:6} eyr:{eyr:6} hgt:{hgt:7} hcl:{hcl:8} ecl:{ecl:7} pid:{pid:11} cid:{cid:6}",
-            byr = self.byr.unwrap_or("-"),
-            iyr = self.iyr.unwrap_or("-"),
-            eyr = self.eyr.unwrap_or("-"),
-            hgt = self.hgt.unwrap_or("-"),
-            hcl = self.hcl.unwrap_or("-"),
-            ecl = self.ecl.unwrap_or("-"),
-            pid = self.pid.unwrap_or("-"),
-            cid = self.cid.unwrap_or("-"),
-        )
+/// Prints the valid passports of the input as JSON Lines or CSV.
+///
+/// Run with `cargo run 4 export [json|csv]`, defaults to JSON Lines.
+fn export(format: Option<&str>) -> crate::Result<()> {
+    let input = crate::read_input("day04.txt")?;
+    let schema = schema::Schema::part2();
+    let passports = parse(&input)?
+        .iter()
+        .filter_map(|r| typed::TypedPassport::from_record(r, &schema).ok())
+        .collect::<Vec<_>>();
+
+    match format {
+        None | Some("json") => passports.iter().for_each(|p| println!("{}", p.to_json())),
+        Some("csv") => {
+            println!("{}", typed::TypedPassport::CSV_HEADER);
+            passports.iter().for_each(|p| println!("{}", p.to_csv()));
+        }
+        Some(f) => return Err(format!("Unknown export format `{}`", f).into()),
     }
+
+    Ok(())
 }
 
-#[derive(Debug, Default)]
-#[allow(dead_code)]
-struct Passport<'a> {
-    pub byr: Option<&'a str>,
-    pub iyr: Option<&'a str>,
-    pub eyr: Option<&'a str>,
-    pub hgt: Option<&'a str>,
-    pub hcl: Option<&'a str>,
-    pub ecl: Option<&'a str>,
-    pub pid: Option<&'a str>,
-    pub cid: Option<&'a str>,
+/// Reads every record of `s`, failing on the first malformed one.
+fn parse(s: &str) -> Result<Vec<Record>, reader::ReadError> {
+    reader::RecordReader::new(s.as_bytes()).collect()
 }
 
-impl Passport<'_> {
-    fn meets_p1_standards(&self) -> bool {
-        self.byr.is_some()
-            && self.iyr.is_some()
-            && self.eyr.is_some()
-            && self.hgt.is_some()
-            && self.hcl.is_some()
-            && self.ecl.is_some()
-            && self.pid.is_some()
-    }
-
-    fn meets_p2_standards(&self) -> bool {
-        self.byr
-            .and_then(|s| s.parse::<usize>().ok())
-            .map(|yr| matches!(yr, 1920..=2002))
-            .unwrap_or(false)
-            && self
-                .iyr
-                .and_then(|s| s.parse::<usize>().ok())
-                .map(|yr| matches!(yr, 2010..=2020))
-                .unwrap_or(false)
-            && self
-                .eyr
-                .and_then(|s| s.parse::<usize>().ok())
-                .map(|yr| matches!(yr, 2020..=2030))
-                .unwrap_or(false)
-            && self
-                .hgt
-                .map(Height::from_str)
-                .map(|res| res.is_ok())
-                .unwrap_or(false)
-            && self.valid_hcl()
-            && self
-                .ecl
-                .map(|s| ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"].contains(&s.trim()))
-                .unwrap_or(false)
-            && self
-                .pid
-                .map(|s| s.chars().filter(|c| matches!(c, '0'..='9')).count() == 9)
-                .unwrap_or(false)
-    }
-
-    fn valid_hcl(&self) -> bool {
-        self.hcl
-            .map(|s| {
-                let mut it = s.chars();
-                it.next().expect("hcl #") == '#' && (it.filter(|c| c.is_digit(16)).count() == 6)
-            })
-            .unwrap_or(false)
+/// A single document from a batch file, its `key:value` pairs in the
+/// order they were read.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// The position of the record in the batch, starting at 0.
+    index: usize,
+    /// The line the record starts on, starting at 1.
+    line: usize,
+    fields: Vec<(String, String)>,
+    /// Pairs with unknown keys, kept aside by [`reader::UnknownKeys::Keep`].
+    extra: Vec<(String, String)>,
+}
+
+impl Record {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, val)| val.as_str())
+    }
+
+    /// Formats the record prefixed with where it was found.
+    fn describe(&self) -> String {
+        format!("#{} (line {}): {}", self.index, self.line, self)
     }
 }
 
-#[allow(dead_code)]
-struct Height {
-    value: usize,
-    unit: HeightUnit,
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs = self
+            .fields
+            .iter()
+            .chain(&self.extra)
+            .map(|(key, val)| format!("{}:{}", key, val))
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", pairs.join(" "))
+    }
+}
+
+/// Streams records out of a batch file.
+///
+/// Records are separated by lines that are empty or only hold whitespace,
+/// and pairs by any whitespace, so CRLF line endings, tabs and repeated
+/// spaces are all fine. Only the record being read is kept in memory.
+mod reader {
+    use super::Record;
+    use std::{
+        collections::HashSet,
+        fmt,
+        io::{self, BufRead},
+    };
+
+    /// What to do with keys a schema does not know about.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum UnknownKeys {
+        /// Fail the record.
+        Error,
+        /// Print a warning on stderr and drop the pair.
+        Warn,
+        /// Keep the pair in [`Record::extra`].
+        Keep,
+    }
+
+    pub struct RecordReader<R> {
+        lines: io::Lines<R>,
+        line: usize,
+        index: usize,
+        known: Option<HashSet<String>>,
+        unknown: UnknownKeys,
+        /// Set once reading fails, nothing more is read after that.
+        failed: bool,
+    }
+
+    impl<R: BufRead> RecordReader<R> {
+        /// A reader accepting any key.
+        pub fn new(inner: R) -> Self {
+            Self {
+                lines: inner.lines(),
+                line: 0,
+                index: 0,
+                known: None,
+                unknown: UnknownKeys::Keep,
+                failed: false,
+            }
+        }
+
+        /// Only `keys` are read into [`Record::fields`],
+        /// any other key is handled according to `policy`.
+        pub fn with_known_keys<K: Into<String>>(
+            mut self,
+            keys: impl IntoIterator<Item = K>,
+            policy: UnknownKeys,
+        ) -> Self {
+            self.known = Some(keys.into_iter().map(Into::into).collect());
+            self.unknown = policy;
+            self
+        }
+
+        fn add_pair(&self, record: &mut Record, pair: &str) -> Result<(), ReadError> {
+            let (key, val) = match pair.split_once(':') {
+                Some((key, val)) if !key.is_empty() => (key, val),
+                _ => {
+                    return Err(ReadError::InvalidPair {
+                        line: self.line,
+                        pair: pair.to_string(),
+                    })
+                }
+            };
+
+            if record
+                .fields
+                .iter()
+                .chain(&record.extra)
+                .any(|(k, _)| k == key)
+            {
+                return Err(ReadError::DuplicateKey {
+                    line: self.line,
+                    record_line: record.line,
+                    key: key.to_string(),
+                });
+            }
+
+            let known = self.known.as_ref().map(|k| k.contains(key)).unwrap_or(true);
+            let pair = (key.to_string(), val.to_string());
+
+            match (known, self.unknown) {
+                (true, _) => record.fields.push(pair),
+                (false, UnknownKeys::Keep) => record.extra.push(pair),
+                (false, UnknownKeys::Warn) => eprintln!(
+                    "warning: line {}: ignoring unknown key `{}`",
+                    self.line, key
+                ),
+                (false, UnknownKeys::Error) => {
+                    return Err(ReadError::UnknownKey {
+                        line: self.line,
+                        key: key.to_string(),
+                    })
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    impl<R: BufRead> Iterator for RecordReader<R> {
+        type Item = Result<Record, ReadError>;
+
+        /// Reads the next record. A malformed record is reported once
+        /// its end is reached, reading then continues with the next one.
+        /// An IO error is reported right away and ends the reading,
+        /// dropping the record it happened in.
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.failed {
+                return None;
+            }
+
+            let mut current: Option<Record> = None;
+            let mut error = None;
+
+            loop {
+                let line = self.lines.next();
+                if line.is_some() {
+                    self.line += 1;
+                }
+
+                let line = match line {
+                    Some(Ok(line)) => line,
+                    Some(Err(err)) => {
+                        if current.is_some() {
+                            self.index += 1;
+                        }
+                        self.failed = true;
+                        return Some(Err(ReadError::Io(err)));
+                    }
+                    None => break,
+                };
+
+                if line.trim().is_empty() {
+                    if current.is_some() {
+                        break;
+                    }
+                    continue;
+                }
+
+                let record = current.get_or_insert_with(|| Record {
+                    index: self.index,
+                    line: self.line,
+                    ..Default::default()
+                });
+
+                for pair in line.split_whitespace() {
+                    if let Err(err) = self.add_pair(record, pair) {
+                        error.get_or_insert(err);
+                    }
+                }
+            }
+
+            let record = current?;
+            self.index += 1;
+
+            match error {
+                Some(err) => Some(Err(err)),
+                None => Some(Ok(record)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum ReadError {
+        Io(io::Error),
+        /// A pair without a `:` or without a key.
+        InvalidPair {
+            line: usize,
+            pair: String,
+        },
+        UnknownKey {
+            line: usize,
+            key: String,
+        },
+        /// A key given twice in the record starting at `record_line`.
+        DuplicateKey {
+            line: usize,
+            record_line: usize,
+            key: String,
+        },
+    }
+
+    impl fmt::Display for ReadError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Self::Io(err) => write!(f, "{}", err),
+                Self::InvalidPair { line, pair } => {
+                    write!(f, "line {}: expected key:value, found `{}`", line, pair)
+                }
+                Self::UnknownKey { line, key } => write!(f, "line {}: unknown key `{}`", line, key),
+                Self::DuplicateKey {
+                    line,
+                    record_line,
+                    key,
+                } => write!(
+                    f,
+                    "line {}: key `{}` given twice in the record starting on line {}",
+                    line, key, record_line
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for ReadError {}
 }
 
-impl FromStr for Height {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let alpha_start = s
-            .chars()
-            .position(|c| !matches!(c, '0'..='9'))
-            .ok_or_else(|| format!("no find end of digits in height str `{}`", s))?;
-
-        let (value_s, unit_s) = s.split_at(alpha_start);
-
-        let value = value_s
-            .parse::<usize>()
-            .map_err(|_| format!("Invalid height: `{}`", s))?;
-
-        let unit = match unit_s {
-            "cm" => {
-                if matches!(value, 150..=193) {
-                    HeightUnit::Cm
-                } else {
-                    return Err(format!(
-                        "Invalid Height `{}`. Cm must be in [150..193]",
-                        value
-                    ));
+/// Validation rules expressed as data.
+///
+/// A schema is a list of fields, each either required or optional and with
+/// a rule its value must follow. Schemas are written one field per line:
+///
+/// ```text
+/// # Comments and blank lines are ignored.
+/// schema passport
+/// mode strict
+/// byr required year 1920..2002
+/// hgt required measure cm=150..193 in=59..76
+/// hcl required pattern #[0-9a-f]{6}
+/// ecl required enum amb blu brn gry grn hzl oth
+/// cid optional any
+/// ```
+///
+/// All ranges are inclusive. Patterns are anchored at both ends and support
+/// literal chars, `.`, classes like `[0-9a-f]` and the quantifiers
+/// `?`, `*`, `+`, `{n}` and `{n,m}`.
+/// Fields not mentioned by the schema are ignored.
+///
+/// Schemas are lenient unless they say `mode strict`, in which case
+/// years must be written with exactly four digits.
+mod schema {
+    use super::Record;
+    use std::{collections::HashMap, fmt, ops::RangeInclusive, str::FromStr};
+
+    const PART1: &str = r#"
+schema passport (part 1)
+byr required any
+iyr required any
+eyr required any
+hgt required any
+hcl required any
+ecl required any
+pid required any
+cid optional any
+"#;
+
+    const PART2: &str = r#"
+schema passport (part 2)
+mode strict
+byr required year 1920..2002
+iyr required year 2010..2020
+eyr required year 2020..2030
+hgt required measure cm=150..193 in=59..76
+hcl required pattern #[0-9a-f]{6}
+ecl required enum amb blu brn gry grn hzl oth
+pid required pattern [0-9]{9}
+cid optional any
+"#;
+
+    #[derive(Debug)]
+    pub struct Schema {
+        pub name: String,
+        pub mode: Mode,
+        pub fields: Vec<Field>,
+    }
+
+    /// How closely values must follow the rules.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Mode {
+        /// Values only have to parse to something within the rules,
+        /// `+2002` and `02002` are both the year 2002.
+        Lenient,
+        /// Values must be written exactly as the rules say.
+        Strict,
+    }
+
+    #[derive(Debug)]
+    pub struct Field {
+        pub name: String,
+        pub required: bool,
+        pub rule: Rule,
+    }
+
+    #[derive(Debug)]
+    pub enum Rule {
+        /// Any value is accepted.
+        Any,
+        /// A year within the range.
+        Year(RangeInclusive<u32>),
+        /// A number directly followed by one of the units,
+        /// within the range of that unit.
+        Measure(Vec<(String, RangeInclusive<u32>)>),
+        /// The whole value matches the pattern.
+        Pattern(Pattern),
+        /// Exactly one of the values.
+        OneOf(Vec<String>),
+    }
+
+    impl Schema {
+        /// Only checks that the required fields are present.
+        pub fn part1() -> Self {
+            PART1.parse().expect("Parsing built in part 1 schema")
+        }
+
+        /// The full set of rules for passports.
+        pub fn part2() -> Self {
+            PART2.parse().expect("Parsing built in part 2 schema")
+        }
+
+        pub fn is_valid(&self, record: &Record) -> bool {
+            self.validate(record).is_empty()
+        }
+
+        /// Returns every rule the record breaks, in schema order.
+        pub fn validate(&self, record: &Record) -> Vec<Violation> {
+            self.fields
+                .iter()
+                .filter_map(|field| {
+                    let kind = match record.get(&field.name) {
+                        Some(val) => field.rule.check(val, self.mode).err()?,
+                        None if field.required => ViolationKind::Missing,
+                        None => return None,
+                    };
+
+                    Some(Violation {
+                        field: field.name.clone(),
+                        kind,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    impl Rule {
+        pub fn check(&self, val: &str, mode: Mode) -> Result<(), ViolationKind> {
+            let value = || val.to_string();
+
+            match self {
+                Self::Any => Ok(()),
+                Self::Year(range) => {
+                    if mode == Mode::Strict {
+                        if !val.chars().all(|c| c.is_ascii_digit()) {
+                            return Err(ViolationKind::NotANumber(value()));
+                        } else if val.len() != 4 {
+                            return Err(ViolationKind::WrongLength(value(), 4));
+                        }
+                    }
+
+                    let yr = val
+                        .parse::<u32>()
+                        .map_err(|_| ViolationKind::NotANumber(value()))?;
+                    check_range(yr, range, None)
                 }
+                Self::Measure(units) => {
+                    let digits = match val.strip_prefix('+') {
+                        Some(digits) if mode == Mode::Lenient => digits,
+                        _ => val,
+                    };
+                    let split = digits
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(digits.len());
+                    let (num, unit) = digits.split_at(split);
+                    if mode == Mode::Strict && num.len() > 1 && num.starts_with('0') {
+                        return Err(ViolationKind::NotANumber(value()));
+                    }
+
+                    let n = num
+                        .parse::<u32>()
+                        .map_err(|_| ViolationKind::NotANumber(value()))?;
+                    let (unit, range) = units
+                        .iter()
+                        .find(|(u, _)| u == unit)
+                        .ok_or_else(|| ViolationKind::UnknownUnit(value()))?;
+                    check_range(n, range, Some(unit))
+                }
+                Self::Pattern(pattern) if pattern.matches(val) => Ok(()),
+                Self::Pattern(pattern) => match pattern.fixed_len() {
+                    Some(expected) if val.chars().count() != expected => {
+                        Err(ViolationKind::WrongLength(value(), expected))
+                    }
+                    _ => Err(ViolationKind::NoMatch(value(), pattern.to_string())),
+                },
+                Self::OneOf(values) if values.iter().any(|v| v == val) => Ok(()),
+                Self::OneOf(_) => Err(ViolationKind::NotOneOf(value())),
             }
-            "in" => {
-                if matches!(value, 56..=76) {
-                    HeightUnit::In
-                } else {
-                    return Err(format!(
-                        "Invalid Height `{}`. In must be in [150..193]",
-                        value
-                    ));
+        }
+    }
+
+    fn check_range(
+        n: u32,
+        range: &RangeInclusive<u32>,
+        unit: Option<&str>,
+    ) -> Result<(), ViolationKind> {
+        if range.contains(&n) {
+            Ok(())
+        } else {
+            Err(ViolationKind::OutOfRange {
+                value: n,
+                unit: unit.map(String::from),
+                min: *range.start(),
+                max: *range.end(),
+            })
+        }
+    }
+
+    /// A rule broken by a field of a record.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Violation {
+        pub field: String,
+        pub kind: ViolationKind,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ViolationKind {
+        Missing,
+        NotANumber(String),
+        UnknownUnit(String),
+        OutOfRange {
+            value: u32,
+            unit: Option<String>,
+            min: u32,
+            max: u32,
+        },
+        /// The value and the length the pattern requires.
+        WrongLength(String, usize),
+        /// The value and the pattern it does not match.
+        NoMatch(String, String),
+        NotOneOf(String),
+        /// The value and why it could not be converted to its type.
+        Invalid(String, String),
+    }
+
+    impl ViolationKind {
+        /// A short description of the kind, without any values.
+        pub fn reason(&self) -> &'static str {
+            match self {
+                Self::Missing => "missing",
+                Self::NotANumber(_) => "not a number",
+                Self::UnknownUnit(_) => "unknown unit",
+                Self::OutOfRange { .. } => "out of range",
+                Self::WrongLength(..) => "wrong length",
+                Self::NoMatch(..) => "bad format",
+                Self::NotOneOf(_) => "unknown value",
+                Self::Invalid(..) => "invalid value",
+            }
+        }
+    }
+
+    impl fmt::Display for Violation {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}: ", self.field)?;
+            match &self.kind {
+                ViolationKind::Missing => write!(f, "missing"),
+                ViolationKind::NotANumber(v) => write!(f, "`{}` is not a number", v),
+                ViolationKind::UnknownUnit(v) => write!(f, "`{}` has an unknown unit", v),
+                ViolationKind::OutOfRange {
+                    value,
+                    unit,
+                    min,
+                    max,
+                } => {
+                    let unit = unit.as_deref().unwrap_or("");
+                    write!(
+                        f,
+                        "{}{} is out of range {}{}..={}{}",
+                        value, unit, min, unit, max, unit
+                    )
+                }
+                ViolationKind::WrongLength(v, len) => write!(
+                    f,
+                    "`{}` is {} chars long, expected {}",
+                    v,
+                    v.chars().count(),
+                    len
+                ),
+                ViolationKind::NoMatch(v, pattern) => {
+                    write!(f, "`{}` does not match `{}`", v, pattern)
+                }
+                ViolationKind::NotOneOf(v) => write!(f, "`{}` is not an allowed value", v),
+                ViolationKind::Invalid(v, err) => write!(f, "`{}` is invalid: {}", v, err),
+            }
+        }
+    }
+
+    /// Counts the violations per field and reason,
+    /// ordered from the most to the least common.
+    pub fn histogram<'a>(
+        violations: impl IntoIterator<Item = &'a Violation>,
+    ) -> Vec<(String, usize)> {
+        let mut counts = HashMap::new();
+        tally(&mut counts, violations);
+        ranked(counts)
+    }
+
+    /// Adds the violations to the running counts per field and reason.
+    pub fn tally<'a>(
+        counts: &mut HashMap<String, usize>,
+        violations: impl IntoIterator<Item = &'a Violation>,
+    ) {
+        for v in violations {
+            *counts
+                .entry(format!("{}: {}", v.field, v.kind.reason()))
+                .or_default() += 1;
+        }
+    }
+
+    /// Orders counts from [`tally`] from the most to the least common.
+    pub fn ranked(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|(r1, n1), (r2, n2)| n2.cmp(n1).then_with(|| r1.cmp(r2)));
+        counts
+    }
+
+    impl FromStr for Schema {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let mut name = None;
+            let mut mode = Mode::Lenient;
+            let mut fields = Vec::<Field>::new();
+
+            for (i, line) in s.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                let err = |msg: String| format!("line {}: {}", i + 1, msg);
+
+                if let Some(rest) = line.strip_prefix("schema ") {
+                    name = Some(rest.trim().to_string());
+                    continue;
                 }
+
+                if let Some(rest) = line.strip_prefix("mode ") {
+                    mode = match rest.trim() {
+                        "strict" => Mode::Strict,
+                        "lenient" => Mode::Lenient,
+                        m => return Err(err(format!("unknown mode `{}`", m))),
+                    };
+                    continue;
+                }
+
+                let field = line.parse::<Field>().map_err(err)?;
+                if fields.iter().any(|f| f.name == field.name) {
+                    return Err(err(format!("field `{}` defined twice", field.name)));
+                }
+                fields.push(field);
+            }
+
+            Ok(Self {
+                name: name.unwrap_or_else(|| "unnamed".to_string()),
+                mode,
+                fields,
+            })
+        }
+    }
+
+    impl FromStr for Field {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let mut words = s.split_whitespace();
+            let name = words.next().ok_or("missing field name")?.to_string();
+            let required = match words.next() {
+                Some("required") => true,
+                Some("optional") => false,
+                Some(w) => return Err(format!("expected required or optional, found `{}`", w)),
+                None => return Err(format!("missing required or optional for `{}`", name)),
+            };
+
+            let rule = match words.next() {
+                Some("any") => Rule::Any,
+                Some("year") => Rule::Year(parse_range(words.next().unwrap_or(""))?),
+                Some("measure") => Rule::Measure(
+                    words
+                        .by_ref()
+                        .map(|w| {
+                            let (unit, range) = w
+                                .split_once('=')
+                                .ok_or_else(|| format!("expected UNIT=MIN..MAX, found `{}`", w))?;
+                            Ok((unit.to_string(), parse_range(range)?))
+                        })
+                        .collect::<Result<_, String>>()?,
+                ),
+                Some("pattern") => Rule::Pattern(words.next().unwrap_or("").parse()?),
+                Some("enum") => Rule::OneOf(words.by_ref().map(String::from).collect()),
+                Some(w) => return Err(format!("unknown rule `{}`", w)),
+                None => return Err(format!("missing rule for `{}`", name)),
+            };
+
+            if let Some(w) = words.next() {
+                return Err(format!("unexpected `{}` after rule", w));
             }
 
-            s => return Err(format!("Unknown height unit `{}`", s)),
+            Ok(Self {
+                name,
+                required,
+                rule,
+            })
+        }
+    }
+
+    /// Parses an inclusive range on the form `MIN..MAX` or `MIN..=MAX`.
+    fn parse_range(s: &str) -> Result<RangeInclusive<u32>, String> {
+        let (min, max) = s
+            .split_once("..")
+            .ok_or_else(|| format!("expected range MIN..MAX, found `{}`", s))?;
+        let max = max.trim_start_matches('=');
+
+        let parse = |n: &str| {
+            n.parse::<u32>()
+                .map_err(|_| format!("invalid number `{}` in range `{}`", n, s))
         };
 
-        Ok(Height { value, unit })
+        Ok(parse(min)?..=parse(max)?)
+    }
+
+    /// A small anchored pattern language, see the module docs.
+    #[derive(Debug)]
+    pub struct Pattern {
+        source: String,
+        atoms: Vec<(Atom, usize, usize)>,
+    }
+
+    /// A single char matcher, repeated between min and max times.
+    #[derive(Debug)]
+    enum Atom {
+        Char(char),
+        AnyChar,
+        Class(Vec<RangeInclusive<char>>),
+    }
+
+    impl Atom {
+        fn matches(&self, c: char) -> bool {
+            match self {
+                Self::Char(a) => *a == c,
+                Self::AnyChar => true,
+                Self::Class(ranges) => ranges.iter().any(|r| r.contains(&c)),
+            }
+        }
+    }
+
+    impl Pattern {
+        /// Returns the number of chars every match has, if it is fixed.
+        pub fn fixed_len(&self) -> Option<usize> {
+            self.atoms
+                .iter()
+                .map(|(_, min, max)| if min == max { Some(*min) } else { None })
+                .sum()
+        }
+
+        pub fn matches(&self, s: &str) -> bool {
+            let chars = s.chars().collect::<Vec<_>>();
+            Self::match_from(&self.atoms, &chars)
+        }
+
+        fn match_from(atoms: &[(Atom, usize, usize)], s: &[char]) -> bool {
+            let (atom, min, max) = match atoms.first() {
+                Some(a) => a,
+                None => return s.is_empty(),
+            };
+
+            let available = s
+                .iter()
+                .take(*max)
+                .take_while(|&&c| atom.matches(c))
+                .count();
+            (*min..=available)
+                .rev()
+                .any(|n| Self::match_from(&atoms[1..], &s[n..]))
+        }
+    }
+
+    impl FromStr for Pattern {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            if s.is_empty() {
+                return Err("empty pattern".to_string());
+            }
+
+            let err = |msg: &str| format!("{} in pattern `{}`", msg, s);
+            let mut atoms = Vec::new();
+            let mut chars = s.chars().peekable();
+
+            while let Some(c) = chars.next() {
+                let atom = match c {
+                    '.' => Atom::AnyChar,
+                    '[' => {
+                        let mut ranges = Vec::new();
+                        loop {
+                            match chars.next() {
+                                Some(']') if !ranges.is_empty() => break,
+                                Some(lo) => {
+                                    if chars.peek() == Some(&'-') {
+                                        chars.next();
+                                        let hi =
+                                            chars.next().ok_or_else(|| err("unclosed class"))?;
+                                        ranges.push(lo..=hi);
+                                    } else {
+                                        ranges.push(lo..=lo);
+                                    }
+                                }
+                                None => return Err(err("unclosed class")),
+                            }
+                        }
+                        Atom::Class(ranges)
+                    }
+                    '\\' => Atom::Char(chars.next().ok_or_else(|| err("trailing escape"))?),
+                    '?' | '*' | '+' | '{' => {
+                        return Err(err("quantifier without anything to repeat"))
+                    }
+                    c => Atom::Char(c),
+                };
+
+                let quantifier = match chars.peek() {
+                    Some('?' | '*' | '+' | '{') => chars.next(),
+                    _ => None,
+                };
+                let (min, max) = match quantifier {
+                    Some('?') => (0, 1),
+                    Some('*') => (0, usize::MAX),
+                    Some('+') => (1, usize::MAX),
+                    Some(_) => {
+                        let mut body = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('}') => break,
+                                Some(c) => body.push(c),
+                                None => return Err(err("unclosed repetition")),
+                            }
+                        }
+                        let num = |n: &str| {
+                            n.trim()
+                                .parse::<usize>()
+                                .map_err(|_| err("invalid repetition"))
+                        };
+                        let (min, max) = match body.split_once(',') {
+                            Some((min, max)) => (num(min)?, num(max)?),
+                            None => (num(&body)?, num(&body)?),
+                        };
+                        if max < min {
+                            return Err(err("repetition max is less than min"));
+                        }
+                        (min, max)
+                    }
+                    None => (1, 1),
+                };
+
+                atoms.push((atom, min, max));
+            }
+
+            Ok(Self {
+                source: s.to_string(),
+                atoms,
+            })
+        }
+    }
+
+    impl fmt::Display for Pattern {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.source)
+        }
     }
 }
 
-enum HeightUnit {
-    Cm,
-    In,
+/// Passports with every field parsed into a proper type.
+mod typed {
+    use super::{
+        schema::{Schema, Violation},
+        Record,
+    };
+    use std::{fmt, str::FromStr};
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct TypedPassport {
+        pub byr: u16,
+        pub iyr: u16,
+        pub eyr: u16,
+        pub hgt: Height,
+        pub hcl: HairColor,
+        pub ecl: EyeColor,
+        pub pid: PassportId,
+        pub cid: Option<String>,
+    }
+
+    impl TypedPassport {
+        pub const CSV_HEADER: &'static str = "byr,iyr,eyr,hgt,hgt_unit,hgt_cm,hcl,ecl,pid,cid";
+
+        /// Validates `record` against `schema` and converts it.
+        /// The schema must at least require the fields of the part 2 spec.
+        pub fn from_record(record: &Record, schema: &Schema) -> Result<Self, Vec<Violation>> {
+            let violations = schema.validate(record);
+            if !violations.is_empty() {
+                return Err(violations);
+            }
+
+            Self::convert(record).map_err(|v| vec![v])
+        }
+
+        fn convert(record: &Record) -> Result<Self, Violation> {
+            fn field<T>(record: &Record, name: &str) -> Result<T, Violation>
+            where
+                T: FromStr,
+                T::Err: fmt::Display,
+            {
+                use super::schema::ViolationKind;
+
+                let violation = |kind| Violation {
+                    field: name.to_string(),
+                    kind,
+                };
+                let val = record
+                    .get(name)
+                    .ok_or_else(|| violation(ViolationKind::Missing))?;
+                val.parse::<T>().map_err(|err| {
+                    violation(ViolationKind::Invalid(val.to_string(), err.to_string()))
+                })
+            }
+
+            Ok(Self {
+                byr: field(record, "byr")?,
+                iyr: field(record, "iyr")?,
+                eyr: field(record, "eyr")?,
+                hgt: field(record, "hgt")?,
+                hcl: field(record, "hcl")?,
+                ecl: field(record, "ecl")?,
+                pid: field(record, "pid")?,
+                cid: record.get("cid").map(String::from),
+            })
+        }
+
+        pub fn to_json(&self) -> String {
+            format!(
+                r#"{{"byr":{},"iyr":{},"eyr":{},"hgt":{{"value":{},"unit":"{}","cm":{:.2}}},"hcl":"{}","ecl":"{}","pid":"{}","cid":{}}}"#,
+                self.byr,
+                self.iyr,
+                self.eyr,
+                self.hgt.value,
+                self.hgt.unit,
+                self.hgt.to_cm(),
+                self.hcl,
+                self.ecl,
+                self.pid,
+                self.cid
+                    .as_deref()
+                    .map(json_string)
+                    .unwrap_or_else(|| "null".to_string()),
+            )
+        }
+
+        /// Formats the passport as a CSV row matching [`Self::CSV_HEADER`].
+        pub fn to_csv(&self) -> String {
+            format!(
+                "{},{},{},{},{},{:.2},{},{},{},{}",
+                self.byr,
+                self.iyr,
+                self.eyr,
+                self.hgt.value,
+                self.hgt.unit,
+                self.hgt.to_cm(),
+                self.hcl,
+                self.ecl,
+                self.pid,
+                self.cid.as_deref().map(csv_field).unwrap_or_default(),
+            )
+        }
+    }
+
+    fn json_string(s: &str) -> String {
+        let mut out = String::from("\"");
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+        out
+    }
+
+    fn csv_field(s: &str) -> String {
+        if s.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Height {
+        pub value: u16,
+        pub unit: HeightUnit,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum HeightUnit {
+        Cm,
+        In,
+    }
+
+    impl Height {
+        pub fn to_cm(self) -> f64 {
+            match self.unit {
+                HeightUnit::Cm => f64::from(self.value),
+                HeightUnit::In => f64::from(self.value) * 2.54,
+            }
+        }
+    }
+
+    impl FromStr for Height {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let alpha_start = s
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(|| format!("Missing unit in height `{}`", s))?;
+            let (value_s, unit_s) = s.split_at(alpha_start);
+
+            let value = value_s
+                .parse::<u16>()
+                .map_err(|_| format!("Invalid height: `{}`", s))?;
+
+            let unit = match unit_s {
+                "cm" => HeightUnit::Cm,
+                "in" => HeightUnit::In,
+                u => return Err(format!("Unknown height unit `{}`", u)),
+            };
+
+            Ok(Self { value, unit })
+        }
+    }
+
+    impl fmt::Display for HeightUnit {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Self::Cm => write!(f, "cm"),
+                Self::In => write!(f, "in"),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct HairColor {
+        pub r: u8,
+        pub g: u8,
+        pub b: u8,
+    }
+
+    impl FromStr for HairColor {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let err = || format!("Invalid hair color `{}`", s);
+            let hex = s.strip_prefix('#').ok_or_else(err)?;
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(err());
+            }
+
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| err());
+            Ok(Self {
+                r: channel(0)?,
+                g: channel(2)?,
+                b: channel(4)?,
+            })
+        }
+    }
+
+    impl fmt::Display for HairColor {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum EyeColor {
+        Amb,
+        Blu,
+        Brn,
+        Gry,
+        Grn,
+        Hzl,
+        Oth,
+    }
+
+    impl FromStr for EyeColor {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "amb" => Ok(Self::Amb),
+                "blu" => Ok(Self::Blu),
+                "brn" => Ok(Self::Brn),
+                "gry" => Ok(Self::Gry),
+                "grn" => Ok(Self::Grn),
+                "hzl" => Ok(Self::Hzl),
+                "oth" => Ok(Self::Oth),
+                s => Err(format!("Unknown eye color `{}`", s)),
+            }
+        }
+    }
+
+    impl fmt::Display for EyeColor {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let s = match self {
+                Self::Amb => "amb",
+                Self::Blu => "blu",
+                Self::Brn => "brn",
+                Self::Gry => "gry",
+                Self::Grn => "grn",
+                Self::Hzl => "hzl",
+                Self::Oth => "oth",
+            };
+            write!(f, "{}", s)
+        }
+    }
+
+    /// A nine digit passport id, displayed with its leading zeroes.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PassportId(pub u32);
+
+    impl FromStr for PassportId {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            if s.len() != 9 || !s.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("Passport id `{}` is not nine digits", s));
+            }
+
+            s.parse()
+                .map(Self)
+                .map_err(|_| format!("Invalid passport id `{}`", s))
+        }
+    }
+
+    impl fmt::Display for PassportId {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{:09}", self.0)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -367,13 +1400,13 @@ hcl:#cfa07d eyr:2025 pid:166559648
 iyr:2011 ecl:brn hgt:59in
 "#;
 
-        assert_eq!(super::part1(example), 2);
+        assert_eq!(super::part1(example).expect("Reading records"), 2);
     }
 
     #[test]
     fn part1() {
         let input = crate::read_input("day04.txt").expect("reading input");
-        assert_eq!(super::part1(&input), 170);
+        assert_eq!(super::part1(&input).expect("Reading records"), 170);
     }
 
     #[test]
@@ -394,7 +1427,7 @@ eyr:2038 hcl:74454a iyr:2023
 pid:3556412378 byr:2007
 "#;
 
-        assert_eq!(super::part2(s), 0);
+        assert_eq!(super::part2(s).expect("Reading records"), 0);
     }
 
     #[test]
@@ -414,30 +1447,414 @@ eyr:2022
 iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719
 "#;
 
-        assert_eq!(super::part2(s), 4);
+        assert_eq!(super::part2(s).expect("Reading records"), 4);
     }
 
     #[test]
     fn valid_hcl() {
-        let tests = vec!["#15bd97", "#18171d", "#7d3b0c"];
+        use super::schema::Mode::Strict;
+
+        let schema = super::schema::Schema::part2();
+        let hcl = schema
+            .fields
+            .iter()
+            .find(|f| f.name == "hcl")
+            .expect("hcl field");
 
-        for t in tests {
+        for t in ["#15bd97", "#18171d", "#7d3b0c", "#c0946f"] {
             assert!(
-                super::Passport {
-                    hcl: Some("#c0946f"),
-                    ..Default::default()
-                }
-                .valid_hcl(),
+                hcl.rule.check(t, Strict).is_ok(),
                 "{} should be valid hcl",
                 t
             );
         }
+
+        for t in ["#123abz", "123abc", "#123abc0", ""] {
+            assert!(
+                hcl.rule.check(t, Strict).is_err(),
+                "{} should be invalid hcl",
+                t
+            );
+        }
+    }
+
+    #[test]
+    fn violations() {
+        use super::schema::{Schema, ViolationKind::*};
+
+        let input = r#"
+hgt:190in byr:19x7 hcl:#12345z ecl:wat pid:12345
+eyr:2020
+
+iyr:2010 hgt:170cm byr:1920 hcl:#123456 ecl:amb
+pid:012345678 eyr:2020
+"#;
+        let records = super::parse(input).expect("Reading records");
+        let schema = Schema::part2();
+
+        assert_eq!((records[0].index, records[0].line), (0, 2));
+        assert_eq!((records[1].index, records[1].line), (1, 5));
+        assert!(schema.validate(&records[1]).is_empty());
+
+        let violations = schema.validate(&records[0]);
+        let kinds = violations.iter().map(|v| &v.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                &NotANumber("19x7".to_string()),
+                &Missing,
+                &OutOfRange {
+                    value: 190,
+                    unit: Some("in".to_string()),
+                    min: 59,
+                    max: 76
+                },
+                &NoMatch("#12345z".to_string(), "#[0-9a-f]{6}".to_string()),
+                &NotOneOf("wat".to_string()),
+                &WrongLength("12345".to_string(), 9),
+            ]
+        );
+        assert_eq!(
+            violations[2].to_string(),
+            "hgt: 190in is out of range 59in..=76in"
+        );
+        assert_eq!(violations[1].field, "iyr");
+
+        let histogram = super::schema::histogram(violations.iter().chain(&violations[..2]));
+        assert_eq!(histogram[0], ("byr: not a number".to_string(), 2));
+        assert_eq!(histogram[1], ("iyr: missing".to_string(), 2));
+        assert_eq!(histogram.len(), 6);
+    }
+
+    /// Each field of the part 2 spec, right on and just past its edges.
+    #[test]
+    fn strict_spec_edge_cases() {
+        let schema = super::schema::Schema::part2();
+        let rule = |name: &str| {
+            &schema
+                .fields
+                .iter()
+                .find(|f| f.name == name)
+                .expect("field in schema")
+                .rule
+        };
+
+        let cases = [
+            ("byr", "1920", true),
+            ("byr", "2002", true),
+            ("byr", "1919", false),
+            ("byr", "2003", false),
+            ("byr", "02002", false),
+            ("byr", "+2002", false),
+            ("byr", "200", false),
+            ("byr", "", false),
+            ("iyr", "2010", true),
+            ("iyr", "2020", true),
+            ("iyr", "2009", false),
+            ("iyr", "2021", false),
+            ("eyr", "2020", true),
+            ("eyr", "2030", true),
+            ("eyr", "2031", false),
+            ("eyr", "20300", false),
+            ("hgt", "150cm", true),
+            ("hgt", "193cm", true),
+            ("hgt", "149cm", false),
+            ("hgt", "194cm", false),
+            ("hgt", "59in", true),
+            ("hgt", "76in", true),
+            ("hgt", "56in", false),
+            ("hgt", "58in", false),
+            ("hgt", "77in", false),
+            ("hgt", "190", false),
+            ("hgt", "cm", false),
+            ("hgt", "170CM", false),
+            ("hgt", "", false),
+            ("hcl", "#123abc", true),
+            ("hcl", "#123abz", false),
+            ("hcl", "#123ABC", false),
+            ("hcl", "#123abcd", false),
+            ("hcl", "#12345g6", false),
+            ("hcl", "#12345", false),
+            ("hcl", "123abc", false),
+            ("hcl", "#", false),
+            ("hcl", "", false),
+            ("ecl", "brn", true),
+            ("ecl", "wat", false),
+            ("ecl", "BRN", false),
+            ("ecl", "brnn", false),
+            ("ecl", "", false),
+            ("pid", "000000001", true),
+            ("pid", "0123456789", false),
+            ("pid", "12345678", false),
+            ("pid", "12345678a", false),
+            ("pid", "1234a56789", false),
+            ("pid", "+12345678", false),
+            ("pid", "", false),
+        ];
+
+        for (field, val, valid) in cases.iter() {
+            assert_eq!(
+                rule(field).check(val, schema.mode).is_ok(),
+                *valid,
+                "{}:{} should be {}",
+                field,
+                val,
+                if *valid { "valid" } else { "invalid" }
+            );
+        }
+    }
+
+    #[test]
+    fn lenient_years() {
+        use super::schema::{Mode, Schema};
+
+        let schema = "byr required year 1920..2002"
+            .parse::<Schema>()
+            .expect("parsing schema");
+        let byr = &schema.fields[0].rule;
+
+        assert_eq!(schema.mode, Mode::Lenient);
+        for yr in ["2002", "02002", "+2002"] {
+            assert!(byr.check(yr, Mode::Lenient).is_ok(), "{} is lenient", yr);
+        }
+        assert!(byr.check("02002", Mode::Strict).is_err());
+        assert!(byr.check("+2002", Mode::Strict).is_err());
+        assert!(byr.check("2003", Mode::Lenient).is_err());
+    }
+
+    #[test]
+    fn lenient_measures() {
+        use super::schema::{Mode, Schema, ViolationKind};
+
+        let schema = "hgt required measure cm=150..193"
+            .parse::<Schema>()
+            .expect("parsing schema");
+        let hgt = &schema.fields[0].rule;
+
+        for h in ["150cm", "0150cm", "+150cm"] {
+            assert!(hgt.check(h, Mode::Lenient).is_ok(), "{} is lenient", h);
+        }
+        assert!(hgt.check("150cm", Mode::Strict).is_ok());
+        for h in ["0150cm", "+150cm", "00150cm"] {
+            assert_eq!(
+                hgt.check(h, Mode::Strict),
+                Err(ViolationKind::NotANumber(h.to_string())),
+                "{} is not strict",
+                h
+            );
+        }
+    }
+
+    #[test]
+    fn typed_passports() {
+        use super::typed::{EyeColor, HeightUnit, TypedPassport};
+
+        let input = r#"
+pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980
+hcl:#623a2f
+
+eyr:2029 ecl:blu cid:"1,2" byr:1989
+iyr:2014 pid:896056539 hcl:#a97842 hgt:165cm
+
+hgt:59cm ecl:zzz
+eyr:2038 hcl:74454a iyr:2023
+pid:3556412378 byr:2007
+"#;
+        let schema = super::schema::Schema::part2();
+        let records = super::parse(input).expect("Reading records");
+        let passports = records
+            .iter()
+            .map(|r| TypedPassport::from_record(r, &schema))
+            .collect::<Vec<_>>();
+
+        let p = passports[0].as_ref().expect("valid passport");
+        assert_eq!((p.byr, p.iyr, p.eyr), (1980, 2012, 2030));
+        assert_eq!(p.hgt.unit, HeightUnit::In);
+        assert!((p.hgt.to_cm() - 187.96).abs() < 1e-9);
+        assert_eq!((p.hcl.r, p.hcl.g, p.hcl.b), (0x62, 0x3a, 0x2f));
+        assert_eq!(p.ecl, EyeColor::Grn);
+        assert_eq!(p.pid.to_string(), "087499704");
+        assert_eq!(
+            p.to_json(),
+            r##"{"byr":1980,"iyr":2012,"eyr":2030,"hgt":{"value":74,"unit":"in","cm":187.96},"hcl":"#623a2f","ecl":"grn","pid":"087499704","cid":null}"##
+        );
+        assert_eq!(
+            p.to_csv(),
+            "1980,2012,2030,74,in,187.96,#623a2f,grn,087499704,"
+        );
+
+        let p = passports[1].as_ref().expect("valid passport");
+        assert!(p.to_json().ends_with(r#""cid":"\"1,2\""}"#));
+        assert!(p.to_csv().ends_with(r#",896056539,"""1,2""""#));
+
+        assert_eq!(passports[2].as_ref().err().map(Vec::len), Some(7));
+
+        let loose = "byr required any\niyr required any\neyr required any\nhgt required any\nhcl required any\necl required any\npid required any"
+            .parse::<super::schema::Schema>()
+            .expect("parsing schema");
+        let record = &super::parse("byr:1 iyr:2 eyr:3 hgt:4ft hcl:x ecl:y pid:z")
+            .expect("Reading records")[0];
+        let err = TypedPassport::from_record(record, &loose).expect_err("unconvertible");
+        assert_eq!(
+            err[0].to_string(),
+            "hgt: `4ft` is invalid: Unknown height unit `ft`"
+        );
+    }
+
+    #[test]
+    fn reader_whitespace() {
+        use super::reader::RecordReader;
+        use std::io::BufReader;
+
+        let input = "ecl:gry  pid:860033327\r\neyr:2020\thcl:#fffffd \r\n \t\r\n\r\n\tiyr:2013 ecl:amb\r\n  \nbyr:1929";
+        // A tiny buffer makes sure records are put together across reads.
+        let records = RecordReader::new(BufReader::with_capacity(4, input.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("reading records");
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].fields.len(), 4);
+        assert_eq!(records[0].get("hcl"), Some("#fffffd"));
+        assert_eq!((records[1].index, records[1].line), (1, 5));
+        assert_eq!(records[1].to_string(), "iyr:2013 ecl:amb");
+        assert_eq!((records[2].index, records[2].line), (2, 7));
+    }
+
+    #[test]
+    fn reader_unknown_and_duplicate_keys() {
+        use super::reader::{ReadError, RecordReader, UnknownKeys};
+
+        let input = "byr:1 foo:x\n\nbyr:2 byr:3\nbar\n\nbyr:4 foo:y";
+        let read = |policy| {
+            RecordReader::new(input.as_bytes())
+                .with_known_keys(vec!["byr"], policy)
+                .collect::<Vec<_>>()
+        };
+
+        let kept = read(UnknownKeys::Keep);
+        let first = kept[0].as_ref().expect("first record");
+        assert_eq!(first.get("foo"), None);
+        assert_eq!(first.extra, vec![("foo".to_string(), "x".to_string())]);
+        assert_eq!(first.to_string(), "byr:1 foo:x");
+
+        // The duplicate is reported before the invalid pair on the next line.
+        match &kept[1] {
+            Err(ReadError::DuplicateKey {
+                line,
+                record_line,
+                key,
+            }) => assert_eq!((*line, *record_line, key.as_str()), (3, 3, "byr")),
+            r => panic!("expected duplicate key, got {:?}", r),
+        }
+        assert_eq!(kept[2].as_ref().map(|r| r.index).ok(), Some(2));
+
+        let warned = read(UnknownKeys::Warn);
+        assert!(warned[0].as_ref().expect("first record").extra.is_empty());
+
+        let failed = read(UnknownKeys::Error);
+        assert_eq!(
+            failed[0].as_ref().map_err(|err| err.to_string()).err(),
+            Some("line 1: unknown key `foo`".to_string())
+        );
+        assert_eq!(failed.len(), 3);
+
+        let invalid = RecordReader::new("byr:1 bar".as_bytes()).next();
+        assert!(matches!(
+            invalid,
+            Some(Err(ReadError::InvalidPair { line: 1, .. }))
+        ));
+    }
+
+    #[test]
+    fn reader_io_errors() {
+        use super::reader::{ReadError, RecordReader};
+        use std::io::{self, BufReader, Read};
+
+        // Invalid UTF-8 on line 3 fails the second record and ends the reading.
+        let input = &b"byr:1\n\nbyr:2\n\xff\n\nbyr:3\n"[..];
+        let mut records = RecordReader::new(input);
+        assert!(matches!(records.next(), Some(Ok(_))));
+        assert!(matches!(records.next(), Some(Err(ReadError::Io(_)))));
+        assert!(records.next().is_none());
+
+        // A reader that keeps failing is only read once.
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "disk on fire"))
+            }
+        }
+        let records = RecordReader::new(BufReader::new(Failing)).collect::<Vec<_>>();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].as_ref().map_err(|err| err.to_string()).err(),
+            Some("disk on fire".to_string())
+        );
+    }
+
+    #[test]
+    fn schema_from_config() {
+        let schema = r#"
+# Library cards
+schema library card
+num required pattern LC-[0-9]{3,5}
+exp required year 2020..2025
+tier optional enum gold silver
+wgt optional measure kg=1..200 lb=2..440
+"#
+        .parse::<super::schema::Schema>()
+        .expect("parsing schema");
+
+        let valid = |s: &str| {
+            super::parse(s)
+                .expect("Reading records")
+                .iter()
+                .all(|r| schema.is_valid(r))
+        };
+        assert_eq!(schema.name, "library card");
+        assert!(valid("num:LC-123 exp:2021"));
+        assert!(valid("num:LC-12345 exp:2025 tier:gold wgt:80kg"));
+        assert!(!valid("num:LC-12 exp:2021"));
+        assert!(!valid("num:LC-123 exp:2021 tier:bronze"));
+        assert!(!valid("num:LC-123 exp:2021 wgt:300kg"));
+        assert!(!valid("exp:2021"));
+    }
+
+    #[test]
+    fn schema_config_errors() {
+        let err = |s: &str| {
+            s.parse::<super::schema::Schema>()
+                .expect_err("schema error")
+        };
+        assert!(err("a sometimes any").starts_with("line 1:"));
+        assert!(err("\na required year 1..").starts_with("line 2:"));
+        assert!(err("a required pattern [a-").contains("unclosed class"));
+        assert!(err("a required pattern a{3").contains("unclosed repetition"));
+        assert!(err("a required pattern a{2,").contains("unclosed repetition"));
+        assert!(err("a required any\na optional any").contains("defined twice"));
+        assert!(err("a required colour").contains("unknown rule"));
+        assert!(err("mode picky").contains("unknown mode"));
+    }
+
+    #[test]
+    fn patterns() {
+        let matches = |p: &str, s: &str| {
+            p.parse::<super::schema::Pattern>()
+                .expect("parsing pattern")
+                .matches(s)
+        };
+        assert!(matches("a+b?c*", "aaac"));
+        assert!(matches("a+b?c*", "ab"));
+        assert!(!matches("a+b?c*", "bc"));
+        assert!(matches("[0-9a-f]{2,3}x", "0fx"));
+        assert!(!matches("[0-9a-f]{2,3}x", "0fa9x"));
+        assert!(matches("\\..", ".é"));
     }
 
     #[test]
     fn part2() {
         let input = crate::read_input("day04.txt").expect("reading input");
-        assert_eq!(super::part2(&input), 103);
+        assert_eq!(super::part2(&input).expect("Reading records"), 103);
     }
 }
 
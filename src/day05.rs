@@ -73,99 +73,642 @@
 //! What is the ID of your seat?
 //!
 
-use std::str::FromStr;
+use std::{fmt, ops::Range, str::FromStr};
 
 pub fn solve() -> crate::Result<()> {
     let input = crate::read_input("day05.txt")?;
-    println!("Day05 part1: {}", part1(&input));
-    println!("Day05 part2: {}", part2(&input));
+
+    match std::env::args().nth(2).as_deref() {
+        Some("map") => print!(
+            "{}",
+            seat_map(&input, &Layout::DEFAULT, Some(part2(&input)?))?
+        ),
+        Some("encode") => {
+            for arg in std::env::args().skip(3) {
+                let id = arg
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid seat id `{}`", arg))?;
+                let pass = encode(id)
+                    .ok_or_else(|| format!("Seat id `{}` is outside of the plane", id))?;
+                println!("{}: {}", id, pass);
+            }
+        }
+        Some("bench") => {
+            let seats = match std::env::args().nth(3) {
+                Some(n) => n
+                    .parse()
+                    .map_err(|_| format!("Invalid seat count `{}`", n))?,
+                None => 1 << 20,
+            };
+            bench::run(seats);
+        }
+        Some("layout") => {
+            let path = std::env::args()
+                .nth(3)
+                .ok_or("Missing layout file argument")?;
+            let layout = std::fs::read_to_string(&path)?
+                .parse::<Layout>()
+                .map_err(|err| format!("{}: {}", path, err))?;
+            let input = match std::env::args().nth(4) {
+                Some(path) => std::fs::read_to_string(path)?,
+                None => input,
+            };
+
+            let seats = decode_all(&input, &layout)?;
+            let ids = seats.iter().map(|&s| layout.id(s)).collect::<Vec<_>>();
+            println!("Highest seat id: {:?}", ids.iter().max());
+            println!("Own seat id: {:?}", find_own_seat(&ids));
+            for gap in gaps(&ids) {
+                println!("Missing: {:?}", gap);
+            }
+        }
+        _ => {
+            println!("Day05 part1: {}", part1(&input)?);
+            println!("Day05 part2: {}", part2(&input)?);
+        }
+    }
+
     Ok(())
 }
 
-fn part1(input: &str) -> usize {
-    input
-        .trim()
-        .lines()
-        .map(|s| Seat::from_str(s).expect("Invalid seat"))
-        .map(|seat| seat.id())
-        .max()
-        .expect("Could not find highest seat")
+fn part1(input: &str) -> crate::Result<usize> {
+    let mut highest = None;
+    for s in input.trim().lines() {
+        highest = highest.max(Some(Seat::from_str(s)?.id()));
+    }
+    Ok(highest.ok_or("Could not find highest seat")?)
+}
+
+fn part2(input: &str) -> crate::Result<usize> {
+    let seats = decode_all(input, &Layout::DEFAULT)?;
+    let ids = seats.into_iter().map(|s| s.id()).collect::<Vec<_>>();
+    Ok(find_own_seat(&ids).ok_or("Could not find the seat!")?)
+}
+
+/// Returns every run of ids missing between the lowest and highest of `ids`, in order.
+/// Uses a bitmap over that span instead of sorting, so this is O(n + span).
+fn gaps(ids: &[usize]) -> Vec<Range<usize>> {
+    let (min, max) = match (ids.iter().min(), ids.iter().max()) {
+        (Some(&min), Some(&max)) => (min, max),
+        _ => return Vec::new(),
+    };
+
+    let mut seen = vec![false; max - min + 1];
+    for &id in ids {
+        seen[id - min] = true;
+    }
+
+    let mut gaps = Vec::new();
+    let mut start = None;
+    for (i, &seen) in seen.iter().enumerate() {
+        match (start, seen) {
+            (None, false) => start = Some(min + i),
+            (Some(s), true) => {
+                gaps.push(s..min + i);
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    gaps
 }
 
-fn part2(input: &str) -> usize {
-    let mut seats = input
+/// Your seat is the only one missing with both neighbours taken.
+fn find_own_seat(ids: &[usize]) -> Option<usize> {
+    gaps(ids)
+        .into_iter()
+        .find(|gap| gap.len() == 1)
+        .map(|gap| gap.start)
+}
+
+fn decode_all(input: &str, layout: &Layout) -> Result<Vec<Seat>, DecodeError> {
+    input
         .trim()
         .lines()
-        .map(|s| Seat::from_str(s).expect("Invalid seat"))
-        .collect::<Vec<_>>();
+        .map(|s| layout.decode(s.trim()))
+        .collect()
+}
 
-    seats.sort_by(|s1, s2| s1.id().cmp(&s2.id()));
+/// Draws every seat of the plane, one row per line with the aisle in the middle.
+/// `#` is a seat on a boarding pass, `.` a missing seat and `@` is `own`.
+fn seat_map(input: &str, layout: &Layout, own: Option<usize>) -> Result<String, DecodeError> {
+    let mut occupied = vec![false; layout.rows * layout.cols];
+    for seat in decode_all(input, layout)? {
+        occupied[seat.row * layout.cols + seat.col] = true;
+    }
 
-    // Iterate over all seats viewing 2 at each iter.
-    // 1: [a b] c d e
-    // 2: a [b c] d e
-    // 3: a b [c d] e
-    for slice in seats.windows(2) {
-        let s1 = &slice[0];
-        let s2 = &slice[1];
+    let width = (layout.rows - 1).to_string().len();
+    let mut out = String::new();
+    for (row, taken) in occupied.chunks(layout.cols).enumerate() {
+        out.push_str(&format!("{:width$} ", row, width = width));
+        for (col, &taken) in taken.iter().enumerate() {
+            if col > 0 && col == layout.cols / 2 {
+                out.push(' ');
+            }
 
-        // If the right id is not one smaller than the prev.
-        // It must be the seat.
-        if s2.id() != s1.id() + 1 {
-            return s1.id() + 1;
+            let id = layout.id(Seat { row, col });
+            out.push(match (Some(id) == own, taken) {
+                (true, _) => '@',
+                (false, true) => '#',
+                (false, false) => '.',
+            });
         }
+        out.push('\n');
     }
 
-    panic!("Could not find the seat!");
+    Ok(out)
 }
 
-impl FromStr for Seat {
-    type Err = String;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut rows = 0..128;
-        let mut cols = 0..8;
+/// The seating of a plane, and how boarding passes describe it.
+///
+/// Layouts can be read from a small config file, where every setting is
+/// optional and defaults to the plane of the puzzle:
+///
+/// ```text
+/// # A small regional jet.
+/// rows = 32
+/// cols = 4
+/// # Front, back, left and right.
+/// letters = FBLR
+/// id = row * 4 + col
+/// ```
+///
+/// Rows and columns must be powers of two, the id is any sum of
+/// `row`, `col` and constants multiplied together, e.g. `100 + 10 * row + col`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    rows: usize,
+    cols: usize,
+    front: char,
+    back: char,
+    left: char,
+    right: char,
+    id: IdFormula,
+}
+
+/// `id = row * self.row + col * self.col + self.offset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IdFormula {
+    row: usize,
+    col: usize,
+    offset: usize,
+}
+
+impl Layout {
+    const DEFAULT: Self = Self {
+        rows: 128,
+        cols: 8,
+        front: 'F',
+        back: 'B',
+        left: 'L',
+        right: 'R',
+        id: IdFormula {
+            row: 8,
+            col: 1,
+            offset: 0,
+        },
+    };
+
+    fn row_bits(&self) -> usize {
+        self.rows.trailing_zeros() as usize
+    }
+
+    fn col_bits(&self) -> usize {
+        self.cols.trailing_zeros() as usize
+    }
 
-        for c in s.chars() {
+    fn decode(&self, pass: &str) -> Result<Seat, DecodeError> {
+        // A pass is a binary number, rows in the high bits and cols in the low.
+        let mut bits = 0;
+        let mut len = 0;
+        for (pos, c) in pass.chars().enumerate() {
+            len += 1;
+            let (zero, one) = if pos < self.row_bits() {
+                (self.front, self.back)
+            } else {
+                (self.left, self.right)
+            };
+
+            bits <<= 1;
             match c {
-                'B' => rows.start += rows.len() / 2,
-                'F' => rows.end -= rows.len() / 2,
-                'R' => cols.start += cols.len() / 2,
-                'L' => cols.end -= cols.len() / 2,
-                c => return Err(format!("`{}`. Invalid char `{}`", s, c)),
+                c if c == one => bits |= 1,
+                c if c == zero => (),
+                found => {
+                    return Err(DecodeError::InvalidChar {
+                        pass: pass.to_string(),
+                        pos,
+                        found,
+                        expected: [zero, one],
+                    })
+                }
             }
         }
 
-        assert_eq!(cols.len(), 1);
-        assert_eq!(rows.len(), 1);
+        let expected = self.row_bits() + self.col_bits();
+        if len != expected {
+            return Err(DecodeError::WrongLength {
+                pass: pass.to_string(),
+                expected,
+            });
+        }
 
         Ok(Seat {
-            row: rows.start,
-            col: cols.start,
+            row: bits >> self.col_bits(),
+            col: bits & (self.cols - 1),
+        })
+    }
+
+    /// Returns the boarding pass of `seat`, which must be on the plane.
+    fn encode(&self, seat: Seat) -> String {
+        assert!(
+            seat.row < self.rows && seat.col < self.cols,
+            "{:?} is outside of the plane",
+            seat
+        );
+
+        let rows = (0..self.row_bits()).rev().map(|bit| {
+            if seat.row & (1 << bit) == 0 {
+                self.front
+            } else {
+                self.back
+            }
+        });
+        let cols = (0..self.col_bits()).rev().map(|bit| {
+            if seat.col & (1 << bit) == 0 {
+                self.left
+            } else {
+                self.right
+            }
+        });
+
+        rows.chain(cols).collect()
+    }
+
+    /// The highest seat id a layout may give,
+    /// which keeps seat maps and the bitmaps of [`gaps`] small.
+    const MAX_ID: usize = 1 << 24;
+
+    /// Panics if the id does not fit, which parsing the layout rules out
+    /// for every seat on the plane.
+    fn id(&self, seat: Seat) -> usize {
+        self.checked_id(seat).expect("seat id fits")
+    }
+
+    fn checked_id(&self, seat: Seat) -> Option<usize> {
+        seat.row
+            .checked_mul(self.id.row)?
+            .checked_add(seat.col.checked_mul(self.id.col)?)?
+            .checked_add(self.id.offset)
+    }
+
+    /// Returns the seat with `id`, if there is one on the plane.
+    fn seat(&self, id: usize) -> Option<Seat> {
+        let IdFormula { row, col, offset } = self.id;
+        let id = id.checked_sub(offset)?;
+
+        // Whichever factor is larger decides the order ids are laid out in.
+        let seat = if row >= col {
+            Seat {
+                row: id / row,
+                col: id % row / col,
+            }
+        } else {
+            Seat {
+                row: id % col / row,
+                col: id / col,
+            }
+        };
+
+        Some(seat).filter(|&s| {
+            s.row < self.rows && s.col < self.cols && self.checked_id(s) == Some(id + offset)
         })
     }
 }
 
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut layout = Self::DEFAULT;
+        let mut id = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = |msg: String| format!("line {}: {}", i + 1, msg);
+            let (key, val) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| err(format!("expected key = value, found `{}`", line)))?;
+
+            let power_of_two = |v: &str| match v.parse::<usize>() {
+                Ok(n) if n.is_power_of_two() => Ok(n),
+                _ => Err(err(format!(
+                    "{} must be a power of two, found `{}`",
+                    key, v
+                ))),
+            };
+
+            match key {
+                "rows" => layout.rows = power_of_two(val)?,
+                "cols" => layout.cols = power_of_two(val)?,
+                "letters" => match val.chars().collect::<Vec<_>>()[..] {
+                    [front, back, left, right] if front != back && left != right => {
+                        layout.front = front;
+                        layout.back = back;
+                        layout.left = left;
+                        layout.right = right;
+                    }
+                    _ => {
+                        return Err(err(format!(
+                            "letters must be front, back, left and right, found `{}`",
+                            val
+                        )))
+                    }
+                },
+                "id" => id = Some(val.parse::<IdFormula>().map_err(err)?),
+                k => return Err(err(format!("unknown setting `{}`", k))),
+            }
+        }
+
+        layout.id = id.unwrap_or(IdFormula {
+            row: layout.cols,
+            col: 1,
+            offset: 0,
+        });
+
+        let IdFormula { row, col, .. } = layout.id;
+        let unique = matches!(col.checked_mul(layout.cols), Some(width) if col > 0 && row >= width)
+            || matches!(row.checked_mul(layout.rows), Some(height) if row > 0 && col >= height);
+        if !unique {
+            return Err("id formula gives several seats the same id".to_string());
+        }
+
+        // Ids grow with both row and col, so the last seat has the highest.
+        let last = Seat {
+            row: layout.rows - 1,
+            col: layout.cols - 1,
+        };
+        match layout.checked_id(last) {
+            Some(id) if id <= Self::MAX_ID => (),
+            _ => {
+                return Err(format!(
+                    "the plane is too large, seat ids must be at most {}",
+                    Self::MAX_ID
+                ))
+            }
+        }
+
+        Ok(layout)
+    }
+}
+
+impl FromStr for IdFormula {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let too_large = || format!("id formula `{}` is too large", s.trim());
+        let mut formula = Self {
+            row: 0,
+            col: 0,
+            offset: 0,
+        };
+
+        for term in s.split('+') {
+            let mut factor = 1usize;
+            let mut var = None;
+            for f in term.split('*').map(|f| f.trim()) {
+                match (f, var) {
+                    ("row", None) | ("col", None) => var = Some(f),
+                    ("row", _) | ("col", _) => {
+                        return Err(format!(
+                            "`{}` has more than one of row and col",
+                            term.trim()
+                        ))
+                    }
+                    (n, _) => {
+                        let n = n
+                            .parse::<usize>()
+                            .map_err(|_| format!("invalid number `{}` in id formula", n))?;
+                        factor = factor.checked_mul(n).ok_or_else(too_large)?;
+                    }
+                }
+            }
+
+            let sum = match var {
+                Some("row") => &mut formula.row,
+                Some(_) => &mut formula.col,
+                None => &mut formula.offset,
+            };
+            *sum = sum.checked_add(factor).ok_or_else(too_large)?;
+        }
+
+        Ok(formula)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DecodeError {
+    WrongLength {
+        pass: String,
+        expected: usize,
+    },
+    /// `pos` is the index of the char in the pass.
+    InvalidChar {
+        pass: String,
+        pos: usize,
+        found: char,
+        expected: [char; 2],
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WrongLength { pass, expected } => write!(
+                f,
+                "`{}` is {} chars long, expected {}",
+                pass,
+                pass.chars().count(),
+                expected
+            ),
+            Self::InvalidChar {
+                pass,
+                pos,
+                found,
+                expected: [a, b],
+            } => write!(
+                f,
+                "`{}`: invalid char `{}` at {}, expected `{}` or `{}`",
+                pass, found, pos, a, b
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl FromStr for Seat {
+    type Err = DecodeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Layout::DEFAULT.decode(s)
+    }
+}
+
+/// Writes the seat back as its boarding pass, e.g. `FBFBBFFRLR`.
+impl fmt::Display for Seat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Layout::DEFAULT.encode(*self))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Seat {
     row: usize,
     col: usize,
 }
 impl Seat {
     fn id(&self) -> usize {
-        self.row * 8 + self.col
+        Layout::DEFAULT.id(*self)
+    }
+
+    /// Returns `None` if `id` is outside of the plane.
+    fn from_id(id: usize) -> Option<Self> {
+        Layout::DEFAULT.seat(id)
+    }
+}
+
+/// Returns the boarding pass of the seat with `id`,
+/// or `None` if `id` is outside of the plane.
+fn encode(id: usize) -> Option<String> {
+    Seat::from_id(id).map(|seat| seat.to_string())
+}
+
+/// Compares the bitwise decoder and bitmap gap search with the
+/// original halving decoder and sort based search.
+/// Run with `cargo run --release 5 bench [seats]`.
+mod bench {
+    use super::{Layout, Seat};
+    use std::time::Instant;
+
+    pub fn run(seats: usize) {
+        let (layout, manifest) = manifest(seats, seats / 3);
+        println!(
+            "{} passes on a plane of {} rows by {} cols",
+            manifest.len(),
+            layout.rows,
+            layout.cols
+        );
+
+        let start = Instant::now();
+        let mut ids = manifest
+            .iter()
+            .map(|pass| layout.id(decode_halving(&layout, pass)))
+            .collect::<Vec<_>>();
+        let decoded = start.elapsed();
+        let own = first_gap_sorted(&mut ids);
+        println!(
+            "halving + sort:  decode {:>10?}  search {:>10?}  seat {:?}",
+            decoded,
+            start.elapsed() - decoded,
+            own
+        );
+
+        let start = Instant::now();
+        let ids = manifest
+            .iter()
+            .map(|pass| layout.decode(pass).map(|s| layout.id(s)))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("decoding generated pass");
+        let decoded = start.elapsed();
+        let own = super::find_own_seat(&ids);
+        println!(
+            "bitwise + gaps:  decode {:>10?}  search {:>10?}  seat {:?}",
+            decoded,
+            start.elapsed() - decoded,
+            own
+        );
     }
 
-    #[cfg(test)]
-    fn from_id(id: usize) -> Self {
-        Self {
-            row: id / 8,
-            col: id % 8,
+    /// Generates boarding passes, in a shuffled order, for a full plane with
+    /// room for at least `seats` seats, except for the seat with id `own`.
+    pub fn manifest(seats: usize, own: usize) -> (Layout, Vec<String>) {
+        let cols = 8;
+        let layout = Layout {
+            rows: (seats / cols).next_power_of_two().max(2),
+            cols,
+            id: super::IdFormula {
+                row: cols,
+                col: 1,
+                offset: 0,
+            },
+            ..Layout::DEFAULT
+        };
+
+        let mut ids = (0..layout.rows * layout.cols)
+            .filter(|&id| id != own)
+            .collect::<Vec<_>>();
+
+        // Fisher-Yates with a small linear congruential generator.
+        let mut state: u64 = 0x2020_1205;
+        for i in (1..ids.len()).rev() {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            ids.swap(i, (state >> 33) as usize % (i + 1));
         }
+
+        let passes = ids
+            .into_iter()
+            .map(|id| layout.encode(layout.seat(id).expect("generated seat")))
+            .collect();
+
+        (layout, passes)
+    }
+
+    /// The original decoder, halving the row and col ranges char by char.
+    pub fn decode_halving(layout: &Layout, pass: &str) -> Seat {
+        let mut rows = 0..layout.rows;
+        let mut cols = 0..layout.cols;
+
+        for c in pass.chars() {
+            match c {
+                c if c == layout.back => rows.start += rows.len() / 2,
+                c if c == layout.front => rows.end -= rows.len() / 2,
+                c if c == layout.right => cols.start += cols.len() / 2,
+                c if c == layout.left => cols.end -= cols.len() / 2,
+                c => panic!("Invalid char `{}` in `{}`", c, pass),
+            }
+        }
+
+        Seat {
+            row: rows.start,
+            col: cols.start,
+        }
+    }
+
+    /// The original search, sorting all ids and returning the first gap.
+    pub fn first_gap_sorted(ids: &mut [usize]) -> Option<usize> {
+        ids.sort_unstable();
+
+        // Iterate over all seats viewing 2 at each iter.
+        // 1: [a b] c d e
+        // 2: a [b c] d e
+        // 3: a b [c d] e
+        ids.windows(2)
+            .find(|slice| slice[1] != slice[0] + 1)
+            .map(|slice| slice[0] + 1)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Seat;
+
     #[test]
     fn part1_example() {
         let tests = vec![
@@ -190,18 +733,168 @@ mod tests {
             .trim()
             .lines()
             .map(|s| s.parse::<Seat>().expect("Parsing seat"))
-            .for_each(|seat| assert_eq!(seat.id(), Seat::from_id(seat.id()).id()));
+            .for_each(|seat| {
+                assert_eq!(
+                    seat.id(),
+                    Seat::from_id(seat.id()).expect("seat on plane").id()
+                )
+            });
+    }
+
+    #[test]
+    fn encode() {
+        for (s, id) in [
+            ("FBFBBFFRLR", 357),
+            ("BFFFBBFRRR", 567),
+            ("FFFBBBFRRR", 119),
+            ("BBFFBBFRLL", 820),
+        ] {
+            assert_eq!(super::encode(id).as_deref(), Some(s));
+            assert_eq!(s.parse::<Seat>().expect("Parsing seat").to_string(), s);
+        }
+    }
+
+    #[test]
+    fn round_trip_every_seat() {
+        for id in 0..128 * 8 {
+            let pass = super::encode(id).expect("seat on plane");
+            let seat = pass.parse::<Seat>().expect("Parsing seat");
+            assert_eq!(seat.id(), id);
+            assert_eq!(Some(seat), Seat::from_id(id));
+        }
+    }
+
+    #[test]
+    fn seat_map() {
+        let map = super::seat_map(
+            "FFFFFFFLLR\nFFFFFFFLRR\nBBBBBBBRRR",
+            &super::Layout::DEFAULT,
+            Some(2),
+        )
+        .expect("drawing map");
+        let lines = map.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 128);
+        assert_eq!(lines[0], "  0 .#@# ....");
+        assert_eq!(lines[1], "  1 .... ....");
+        assert_eq!(lines[127], "127 .... ...#");
+    }
+
+    #[test]
+    fn custom_layout() {
+        let layout =
+            "# Regional jet\nrows = 32\ncols = 4\nletters = UDAZ\nid = 100 + col * 32 + row\n"
+                .parse::<super::Layout>()
+                .expect("parsing layout");
+
+        let seat = layout.decode("DUUDUZA").expect("decoding pass");
+        assert_eq!(seat, Seat { row: 18, col: 2 });
+        assert_eq!(layout.id(seat), 100 + 2 * 32 + 18);
+        assert_eq!(layout.seat(182), Some(seat));
+        assert_eq!(layout.seat(99), None);
+        assert_eq!(layout.seat(100 + 4 * 32), None);
+        assert_eq!(layout.encode(seat), "DUUDUZA");
+
+        for id in 100..100 + 32 * 4 {
+            let seat = layout.seat(id).expect("seat on plane");
+            assert_eq!(layout.decode(&layout.encode(seat)), Ok(seat));
+        }
+    }
+
+    #[test]
+    fn decode_errors() {
+        use super::DecodeError;
+
+        assert_eq!(
+            "FBFBBFFRL".parse::<Seat>(),
+            Err(DecodeError::WrongLength {
+                pass: "FBFBBFFRL".to_string(),
+                expected: 10
+            })
+        );
+        assert_eq!(
+            "FBFBBFFRLF".parse::<Seat>(),
+            Err(DecodeError::InvalidChar {
+                pass: "FBFBBFFRLF".to_string(),
+                pos: 9,
+                found: 'F',
+                expected: ['L', 'R'],
+            })
+        );
+    }
+
+    #[test]
+    fn layout_config_errors() {
+        let err = |s: &str| s.parse::<super::Layout>().expect_err(s);
+        assert_eq!(
+            err("rows = 100"),
+            "line 1: rows must be a power of two, found `100`"
+        );
+        assert_eq!(
+            err("\nletters = FFLR"),
+            "line 2: letters must be front, back, left and right, found `FFLR`"
+        );
+        assert_eq!(err("seats = 4"), "line 1: unknown setting `seats`");
+        assert_eq!(err("rows"), "line 1: expected key = value, found `rows`");
+        assert_eq!(
+            err("id = row * col"),
+            "line 1: `row * col` has more than one of row and col"
+        );
+        assert_eq!(
+            err("id = row * 4 + col"),
+            "id formula gives several seats the same id"
+        );
+        assert_eq!("cols = 4".parse::<super::Layout>().map(|l| l.id.row), Ok(4));
+
+        assert_eq!(
+            err("id = row * 99999999999 * 99999999999"),
+            "line 1: id formula `row * 99999999999 * 99999999999` is too large"
+        );
+        assert_eq!(
+            err("id = row * 18446744073709551615 + row + col"),
+            "line 1: id formula `row * 18446744073709551615 + row + col` is too large"
+        );
+        let too_large = "the plane is too large, seat ids must be at most 16777216";
+        assert_eq!(err("rows = 1099511627776\ncols = 1099511627776"), too_large);
+        assert_eq!(err("id = row * 99999999999 + col"), too_large);
+        assert_eq!(err("id = row * 8 + col + 99999999999"), too_large);
+    }
+
+    #[test]
+    fn gaps() {
+        assert_eq!(super::gaps(&[]), vec![]);
+        assert_eq!(super::gaps(&[7, 3, 4, 10, 5]), vec![6..7, 8..10]);
+        assert_eq!(super::find_own_seat(&[7, 3, 4, 10, 5]), Some(6));
+        assert_eq!(super::find_own_seat(&[3, 4, 7]), None);
+    }
+
+    #[test]
+    fn bench_approaches_agree() {
+        use super::bench;
+
+        let (layout, manifest) = bench::manifest(4096, 1234);
+        let mut ids = manifest
+            .iter()
+            .map(|pass| {
+                let seat = layout.decode(pass).expect("decoding pass");
+                assert_eq!(seat, bench::decode_halving(&layout, pass));
+                layout.id(seat)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(super::gaps(&ids), vec![1234..1235]);
+        assert_eq!(bench::first_gap_sorted(&mut ids), Some(1234));
     }
 
     #[test]
     fn part1() {
         let input = crate::read_input("day05.txt").expect("reading input");
-        assert_eq!(801, super::part1(&input));
+        assert_eq!(801, super::part1(&input).expect("Invalid seat"));
     }
 
     #[test]
     fn part2() {
         let input = crate::read_input("day05.txt").expect("reading input");
-        assert_eq!(597, super::part2(&input));
+        assert_eq!(597, super::part2(&input).expect("Invalid seat"));
     }
 }
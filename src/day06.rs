@@ -109,18 +109,33 @@
 //! For each group, count the number of questions to which everyone answered "yes".
 //! What is the sum of those counts?
 
-use std::collections::{HashMap, HashSet};
+use std::{fmt, str::FromStr};
 
 pub fn solve() -> crate::Result<()> {
     let input = crate::read_input("day06.txt")?;
 
-    match std::env::args().nth(2).as_deref() {
-        Some("1") => println!("Day06 part1: {}", part1(&input)),
-        Some("2") => println!("Day06 part2: {}", part2(&input)),
+    let mut args = std::env::args().skip(2).collect::<Vec<_>>();
+    let mode = match args.first().map(String::as_str) {
+        Some("lenient") => {
+            args.remove(0);
+            Mode::Lenient
+        }
+        _ => Mode::Strict,
+    };
+    let groups = parse(&input, mode)?;
+
+    match args.first().map(String::as_str) {
+        Some("1") => println!("Day06 part1: {}", part1(&groups)),
+        Some("2") => println!("Day06 part2: {}", part2(&groups)),
+        Some("report") => report::run(&groups, args.into_iter().skip(1))?,
+        Some(_) => {
+            let query = args.join(" ").parse::<Query>()?;
+            println!("Day06 {}: {}", query, count(&groups, query));
+        }
 
         _ => {
-            println!("Day06 part1: {}", part1(&input));
-            println!("Day06 part2: {}", part2(&input));
+            println!("Day06 part1: {}", part1(&groups));
+            println!("Day06 part2: {}", part2(&groups));
         }
     };
 
@@ -128,44 +143,428 @@ pub fn solve() -> crate::Result<()> {
 }
 
 /// Count any occurence of a letter.
-fn part1(s: &str) -> usize {
-    s.trim()
-        .split("\n\n")
-        .map(|s| s.trim())
-        .map(|grp: &str| {
-            let unique = grp
-                .chars()
-                .filter(|c| c.is_alphabetic())
-                .collect::<HashSet<char>>();
-            unique.len()
-        })
-        .sum()
+fn part1(groups: &[Group]) -> usize {
+    count(groups, Query::Union)
 }
 
 /// Count only the letters that appears on each row
 /// per group.
 /// Prev wrong answer 3237
-fn part2(s: &str) -> usize {
-    s.trim()
-        .split("\n\n")
-        .map(|s| s.trim())
-        .map(|grp: &str| {
-            let mut yesses = HashMap::<char, usize>::new();
-            let mut no_lines = 0;
-            // let mut all_yes = all_questions.clone();
-            grp.lines().for_each(|line| {
-                no_lines += 1;
-                line.chars()
-                    .for_each(|c| *yesses.entry(c).or_default() += 1)
+fn part2(groups: &[Group]) -> usize {
+    count(groups, Query::Intersection)
+}
+
+/// Sums the number of questions matching `query` in every group.
+fn count(groups: &[Group], query: Query) -> usize {
+    groups.iter().map(|grp| grp.query(query).len()).sum()
+}
+
+/// How forgiving [`parse`] is with the answers on each line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Only `a` to `z`.
+    Strict,
+    /// Also `A` to `Z`, and a `\r` ending the line is ignored.
+    Lenient,
+}
+
+/// Parses groups of people separated by blank lines, one person per line.
+/// Stops at the first char that is not an answer.
+fn parse(s: &str, mode: Mode) -> Result<Vec<Group>, ParseError> {
+    let mut groups = Vec::new();
+    let mut people = Vec::new();
+
+    for (i, line) in s.split('\n').enumerate() {
+        let line = match mode {
+            Mode::Strict => line,
+            Mode::Lenient => line.strip_suffix('\r').unwrap_or(line),
+        };
+
+        if line.is_empty() {
+            if !people.is_empty() {
+                groups.push(Group { people });
+                people = Vec::new();
+            }
+            continue;
+        }
+
+        let answers =
+            Answers::parse(line, mode).map_err(|err| ParseError { line: i + 1, ..err })?;
+        people.push(answers);
+    }
+
+    if !people.is_empty() {
+        groups.push(Group { people });
+    }
+
+    Ok(groups)
+}
+
+/// A char that is not an answer.
+/// `line` and `col` are both 1-indexed, `line` is 0 until known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    col: usize,
+    found: char,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: expected answer `a` to `z`, found {:?}",
+            self.line, self.col, self.found
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The questions `a` to `z` as the lowest 26 bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Answers(u32);
+
+impl Answers {
+    const ALL: Self = Self((1 << 26) - 1);
+
+    fn parse(line: &str, mode: Mode) -> Result<Self, ParseError> {
+        line.chars()
+            .enumerate()
+            .try_fold(Self::default(), |acc, (i, c)| {
+                let c = match mode {
+                    Mode::Strict => c,
+                    Mode::Lenient => c.to_ascii_lowercase(),
+                };
+
+                if !c.is_ascii_lowercase() {
+                    return Err(ParseError {
+                        line: 0,
+                        col: i + 1,
+                        found: c,
+                    });
+                }
+
+                Ok(Self(acc.0 | 1 << (c as u8 - b'a')))
+            })
+    }
+
+    fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    fn contains(self, question: usize) -> bool {
+        self.0 & 1 << question != 0
+    }
+
+    fn from_questions(questions: impl Iterator<Item = usize>) -> Self {
+        Self(questions.fold(0, |acc, q| acc | 1 << q))
+    }
+}
+
+/// Writes the questions as letters, e.g. `abz`.
+impl fmt::Display for Answers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (0..26)
+            .filter(|&q| self.contains(q))
+            .try_for_each(|q| write!(f, "{}", (b'a' + q as u8) as char))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Group {
+    people: Vec<Answers>,
+}
+
+impl Group {
+    fn query(&self, query: Query) -> Answers {
+        match query {
+            Query::Union => Answers(self.people.iter().fold(0, |acc, a| acc | a.0)),
+            Query::Intersection => {
+                Answers(self.people.iter().fold(Answers::ALL.0, |acc, a| acc & a.0))
+            }
+            Query::SymmetricDifference => Answers(self.people.iter().fold(0, |acc, a| acc ^ a.0)),
+            Query::Exactly(k) => {
+                Answers::from_questions((0..26).filter(|&q| self.answered_by(q) == k))
+            }
+            Query::AtLeast(percent) => Answers::from_questions((0..26).filter(|&q| {
+                let n = self.answered_by(q);
+                n > 0 && n * 100 >= percent * self.people.len()
+            })),
+        }
+    }
+
+    /// Number of people in the group that answered yes to `question`.
+    fn answered_by(&self, question: usize) -> usize {
+        self.people.iter().filter(|a| a.contains(question)).count()
+    }
+}
+
+/// A question about each group, answered with the questions matching it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Query {
+    /// Answered by anyone.
+    Union,
+    /// Answered by everyone.
+    Intersection,
+    /// Answered by exactly this many people.
+    Exactly(usize),
+    /// Answered by at least this percent of the group, and by someone.
+    AtLeast(usize),
+    /// Answered by an odd number of people.
+    SymmetricDifference,
+}
+
+/// Parses the query names used on the command line, e.g.
+/// `union`, `intersection`, `exactly 2`, `at-least 50` and `xor`.
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let arg = words.next();
+
+        let number = |what: &str| {
+            arg.ok_or_else(|| format!("`{}` expects {}", name, what))?
+                .trim_end_matches('%')
+                .parse::<usize>()
+                .map_err(|_| format!("`{}` expects {}, found `{}`", name, what, s))
+        };
+
+        let query = match name {
+            "union" | "any" => Self::Union,
+            "intersection" | "all" => Self::Intersection,
+            "xor" | "symmetric-difference" => Self::SymmetricDifference,
+            "exactly" => Self::Exactly(number("a number of people")?),
+            "at-least" => match number("a percentage")? {
+                p if p <= 100 => Self::AtLeast(p),
+                p => return Err(format!("percentage `{}` is above 100", p)),
+            },
+            _ => return Err(format!("Unknown query `{}`", s)),
+        };
+
+        match (query, words.next()) {
+            (Self::Exactly(_), None) | (Self::AtLeast(_), None) => Ok(query),
+            (_, None) if arg.is_none() => Ok(query),
+            _ => Err(format!("Unexpected arguments in query `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Union => write!(f, "union"),
+            Self::Intersection => write!(f, "intersection"),
+            Self::Exactly(k) => write!(f, "exactly {}", k),
+            Self::AtLeast(p) => write!(f, "at-least {}%", p),
+            Self::SymmetricDifference => write!(f, "xor"),
+        }
+    }
+}
+
+/// Statistics over all groups: how often each question was answered,
+/// the answers of each group and which questions are answered together.
+///
+/// Run with `cargo run 6 report [questions|groups|correlation] [csv]`,
+/// defaults to all three sections as text tables.
+mod report {
+    use super::{Answers, Group, Query};
+    use std::fmt::Write;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Format {
+        Table,
+        Csv,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Section {
+        Questions,
+        Groups,
+        Correlation,
+    }
+
+    pub fn run(groups: &[Group], args: impl Iterator<Item = String>) -> crate::Result<()> {
+        let mut format = Format::Table;
+        let mut sections = Vec::new();
+
+        for arg in args {
+            match arg.as_str() {
+                "questions" => sections.push(Section::Questions),
+                "groups" => sections.push(Section::Groups),
+                "correlation" => sections.push(Section::Correlation),
+                "csv" => format = Format::Csv,
+                _ => return Err(format!("Unknown report argument `{}`", arg).into()),
+            }
+        }
+
+        if sections.is_empty() {
+            sections = vec![Section::Questions, Section::Groups, Section::Correlation];
+        }
+
+        let out = sections
+            .into_iter()
+            .map(|section| render(groups, section, format))
+            .collect::<Vec<_>>();
+
+        print!("{}", out.join("\n"));
+        Ok(())
+    }
+
+    pub fn render(groups: &[Group], section: Section, format: Format) -> String {
+        match section {
+            Section::Questions => questions(groups, format),
+            Section::Groups => group_table(groups, format),
+            Section::Correlation => correlation_table(groups, format),
+        }
+    }
+
+    /// Number of groups and people that answered yes to each question.
+    pub fn counts(groups: &[Group]) -> [(usize, usize); 26] {
+        let mut counts = [(0, 0); 26];
+        for grp in groups {
+            let union = grp.query(Query::Union);
+            for (q, (groups, people)) in counts.iter_mut().enumerate() {
+                if union.contains(q) {
+                    *groups += 1;
+                    *people += grp.answered_by(q);
+                }
+            }
+        }
+
+        counts
+    }
+
+    /// The phi coefficient of every pair of questions over all people,
+    /// from -1 when never answered together to 1 when always answered together.
+    /// `None` for questions answered by everyone or no one.
+    pub fn correlation(groups: &[Group]) -> Vec<Vec<Option<f64>>> {
+        let people = groups.iter().flat_map(|grp| &grp.people);
+        let n = people.clone().count() as f64;
+
+        let mut both = [[0usize; 26]; 26];
+        for a in people {
+            for i in (0..26).filter(|&i| a.contains(i)) {
+                for j in (0..26).filter(|&j| a.contains(j)) {
+                    both[i][j] += 1;
+                }
+            }
+        }
+
+        (0..26)
+            .map(|i| {
+                (0..26)
+                    .map(|j| {
+                        let (yes_i, yes_j) = (both[i][i] as f64, both[j][j] as f64);
+                        let spread = yes_i * (n - yes_i) * yes_j * (n - yes_j);
+                        if spread == 0.0 {
+                            return None;
+                        }
+
+                        Some((both[i][j] as f64 * n - yes_i * yes_j) / spread.sqrt())
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn letter(q: usize) -> char {
+        (b'a' + q as u8) as char
+    }
+
+    fn questions(groups: &[Group], format: Format) -> String {
+        let mut out = match format {
+            Format::Table => "question  groups  people\n".to_string(),
+            Format::Csv => "question,groups,people\n".to_string(),
+        };
+
+        for (q, (groups, people)) in counts(groups).iter().enumerate() {
+            match format {
+                Format::Table => writeln!(out, "{:>8}  {:>6}  {:>6}", letter(q), groups, people),
+                Format::Csv => writeln!(out, "{},{},{}", letter(q), groups, people),
+            }
+            .expect("writing report");
+        }
+
+        out
+    }
+
+    fn group_table(groups: &[Group], format: Format) -> String {
+        let mut out = match format {
+            Format::Table => format!(
+                "{:>5}  {:>4}  {:26}  {}\n",
+                "group", "size", "union", "intersection"
+            ),
+            Format::Csv => "group,size,union,intersection\n".to_string(),
+        };
+
+        for (i, grp) in groups.iter().enumerate() {
+            let union: Answers = grp.query(Query::Union);
+            let all = grp.query(Query::Intersection);
+            match format {
+                Format::Table => writeln!(
+                    out,
+                    "{:>5}  {:>4}  {:26}  {}",
+                    i + 1,
+                    grp.people.len(),
+                    union.to_string(),
+                    all
+                ),
+                Format::Csv => writeln!(out, "{},{},{},{}", i + 1, grp.people.len(), union, all),
+            }
+            .expect("writing report");
+        }
+
+        out
+    }
+
+    /// Coefficients are written as whole percent in tables and `-` when undefined.
+    fn correlation_table(groups: &[Group], format: Format) -> String {
+        let matrix = correlation(groups);
+        let mut out = String::new();
+
+        match format {
+            Format::Table => writeln!(
+                out,
+                " {}",
+                (0..26)
+                    .map(|q| format!("{:>5}", letter(q)))
+                    .collect::<String>()
+            ),
+            Format::Csv => writeln!(
+                out,
+                "question,{}",
+                (0..26)
+                    .map(|q| letter(q).to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+        .expect("writing report");
+
+        for (q, row) in matrix.iter().enumerate() {
+            let cells = row.iter().map(|phi| match (phi, format) {
+                (Some(phi), Format::Table) => format!("{:>5.0}", phi * 100.0),
+                (None, Format::Table) => format!("{:>5}", "-"),
+                (Some(phi), Format::Csv) => format!(",{:.3}", phi),
+                (None, Format::Csv) => ",".to_string(),
             });
+            writeln!(out, "{}{}", letter(q), cells.collect::<String>()).expect("writing report");
+        }
 
-            yesses.values().filter(|&n| *n == no_lines).count()
-        })
-        .sum()
+        out
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{parse, Group, Mode, ParseError};
+
+    fn groups(s: &str) -> Vec<Group> {
+        parse(s, Mode::Strict).expect("parsing groups")
+    }
 
     #[test]
     fn part1_example() {
@@ -187,13 +586,13 @@ a
 b
 "#
         .trim();
-        assert_eq!(11, super::part1(s));
+        assert_eq!(11, super::part1(&groups(s)));
     }
 
     #[test]
     fn part1() {
         let input = crate::read_input("day06.txt").expect("reading input");
-        assert_eq!(6551, super::part1(&input));
+        assert_eq!(6551, super::part1(&groups(&input)));
     }
 
     #[test]
@@ -217,12 +616,117 @@ b
 "#
         .trim();
 
-        assert_eq!(6, super::part2(input));
+        assert_eq!(6, super::part2(&groups(input)));
+    }
+
+    #[test]
+    fn queries() {
+        use super::{count, Query};
+
+        let groups = groups("abc\nabd\nae\n\nx\nxy\nz");
+        let answers = |q: &str| {
+            let q = q.parse::<Query>().expect("parsing query");
+            groups
+                .iter()
+                .map(|grp| grp.query(q).to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(answers("union"), ["abcde", "xyz"]);
+        assert_eq!(answers("all"), ["a", ""]);
+        assert_eq!(answers("exactly 1"), ["cde", "yz"]);
+        assert_eq!(answers("exactly 2"), ["b", "x"]);
+        assert_eq!(answers("at-least 50%"), ["ab", "x"]);
+        assert_eq!(answers("at-least 0"), ["abcde", "xyz"]);
+        assert_eq!(answers("xor"), ["acde", "yz"]);
+        assert_eq!(count(&groups, Query::Exactly(1)), 5);
+
+        for bad in &[
+            "exactly",
+            "exactly two",
+            "at-least 101",
+            "union 2",
+            "exactly 1 2",
+            "none",
+        ] {
+            assert!(bad.parse::<Query>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn report() {
+        use super::report;
+
+        let groups = groups("ab\nab\nc\n\na\n\nb");
+        let counts = report::counts(&groups);
+        assert_eq!(counts[0], (2, 3));
+        assert_eq!(counts[1], (2, 3));
+        assert_eq!(counts[2], (1, 1));
+        assert_eq!(counts[3], (0, 0));
+
+        let phi = report::correlation(&groups);
+        let rounded = |i: usize, j: usize| phi[i][j].map(|p| (p * 1000.0).round() / 1000.0);
+        assert_eq!(rounded(0, 0), Some(1.0));
+        assert_eq!(rounded(0, 1), Some(0.167));
+        assert_eq!(rounded(0, 2), Some(-0.612));
+        assert_eq!(rounded(0, 3), None);
+
+        let csv = report::render(&groups, report::Section::Groups, report::Format::Csv);
+        assert_eq!(
+            csv,
+            "group,size,union,intersection\n1,3,abc,\n2,1,a,a\n3,1,b,b\n"
+        );
+    }
+
+    #[test]
+    fn strict_parse() {
+        let err = |s: &str| parse(s, Mode::Strict).expect_err(s);
+        let at = |line, col, found| ParseError { line, col, found };
+
+        assert_eq!(err("abc\naBc"), at(2, 2, 'B'));
+        assert_eq!(err("abc\r\nabc"), at(1, 4, '\r'));
+        assert_eq!(err("ab\n\nb c"), at(3, 2, ' '));
+        assert_eq!(err("aé"), at(1, 2, 'é'));
+        assert_eq!(
+            err("a\n\n\nx1").to_string(),
+            "line 4, column 2: expected answer `a` to `z`, found '1'"
+        );
+
+        // Any number of blank lines separate groups.
+        assert_eq!(groups("\n\nab\n\n\n\nc\n").len(), 2);
+        assert_eq!(groups(""), vec![]);
+    }
+
+    #[test]
+    fn lenient_parse() {
+        let lenient = |s: &str| parse(s, Mode::Lenient);
+
+        assert_eq!(
+            lenient("aBc\r\nABC\r\n\r\nz\r\n"),
+            Ok(groups("abc\nabc\n\nz"))
+        );
+        assert_eq!(
+            lenient("ab\n a"),
+            Err(ParseError {
+                line: 2,
+                col: 1,
+                found: ' '
+            })
+        );
+        assert_eq!(
+            lenient("ab\na\rb"),
+            Err(ParseError {
+                line: 2,
+                col: 2,
+                found: '\r'
+            })
+        );
+        assert!(lenient("Ä").is_err());
     }
 
     #[test]
     fn part2() {
         let input = crate::read_input("day06.txt").expect("reading input");
-        assert_eq!(3358, super::part2(&input));
+        assert_eq!(3358, super::part2(&groups(&input)));
     }
 }
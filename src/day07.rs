@@ -44,134 +44,1026 @@
 //!
 //! How many bag colors can eventually contain at least one shiny gold bag?
 //! (The list of rules is quite long; make sure you get all of it.)
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    ops::Range,
+};
 
+/// Run with `cargo run 7 [roots|leaves|children|containers|validate|bench] [colour=NAME]`,
+/// `cargo run 7 explain [colour=NAME]`, `cargo run 7 paths from=NAME [colour=NAME]`
+/// or `cargo run 7 dot|mermaid [colour=NAME] [scope=all|inside|holders]`.
+/// The colour defaults to `shiny gold`.
 pub fn solve() -> crate::Result<()> {
     let input = crate::read_input("day07.txt")?;
-    println!("Day07 part1: {}", part1::solve(&input));
-    println!("Day07 part2: {}", part2::solve(&input));
+    let graph = BagGraph::parse(&input)?;
+
+    let mut colour = "shiny gold".to_string();
+    let mut mode = None;
+    let mut scope = export::Scope::All;
+    let mut outer = None;
+    for arg in std::env::args().skip(2) {
+        match arg.split_once('=') {
+            Some(("colour", name)) | Some(("color", name)) => colour = name.trim().to_string(),
+            Some(("scope", name)) => scope = name.parse()?,
+            Some(("from", name)) => outer = Some(name.trim().to_string()),
+            None if mode.is_none() => mode = Some(arg),
+            _ => return Err(format!("Unknown argument `{}`", arg).into()),
+        }
+    }
+
+    match mode.as_deref() {
+        Some("bench") => {
+            bench::run();
+            return Ok(());
+        }
+        Some("dot") | Some("mermaid") => {
+            let format = match mode.as_deref() {
+                Some("dot") => export::Format::Dot,
+                _ => export::Format::Mermaid,
+            };
+            let bag = graph.id(&colour);
+            if bag.is_none() && scope != export::Scope::All {
+                return Err(format!("No rule mentions a `{}` bag", colour).into());
+            }
+
+            print!("{}", export::export(&graph, format, scope, bag)?);
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    let problems = graph.validate();
+    if mode.as_deref() == Some("validate") {
+        problems.iter().for_each(|problem| println!("{}", problem));
+        return match problems.len() {
+            0 => Ok(()),
+            n => Err(format!("Found {} problems in the rules", n).into()),
+        };
+    }
+
+    // Cycles make every count meaningless, the rest only deserve a warning.
+    let mut cycles = 0;
+    for problem in &problems {
+        match problem {
+            Problem::Cycle(_) => cycles += 1,
+            _ => eprintln!("Warning: {}", problem),
+        }
+    }
+    if cycles > 0 {
+        return Err(format!(
+            "Refusing to solve rules with cycles, found {}, first {}",
+            cycles,
+            problems
+                .iter()
+                .find(|p| matches!(p, Problem::Cycle(_)))
+                .expect("cycle")
+        )
+        .into());
+    }
+
+    let unknown = || format!("No rule mentions a `{}` bag", colour);
+    match mode.as_deref() {
+        Some("roots") => graph.roots().iter().for_each(|name| println!("{}", name)),
+        Some("leaves") => graph.leaves().iter().for_each(|name| println!("{}", name)),
+        Some("children") => {
+            for (name, count) in graph.direct_children(&colour).ok_or_else(unknown)? {
+                println!("{} {}", count, name);
+            }
+        }
+        Some("containers") => {
+            for name in graph.containers_of(&colour).ok_or_else(unknown)? {
+                println!("{}", name);
+            }
+        }
+        Some("explain") => {
+            let bag = graph.id(&colour).ok_or_else(unknown)?;
+            print!("{}", explain::breakdown(&graph, bag)?);
+            if let Some((child, bags)) = explain::biggest_contributor(&graph, bag)? {
+                println!(
+                    "Biggest contributor: {} with {} bags",
+                    graph.name(child),
+                    bags
+                );
+            }
+        }
+        Some("paths") => {
+            let outer = outer.ok_or("`paths` needs the outer bag as `from=NAME`")?;
+            let from = graph
+                .id(&outer)
+                .ok_or_else(|| format!("No rule mentions a `{}` bag", outer))?;
+            let to = graph.id(&colour).ok_or_else(unknown)?;
+
+            let paths = explain::paths(&graph, from, to)?;
+            print!("{}", explain::path_tree(&graph, &paths));
+            println!(
+                "{} paths, {} {} bags in total",
+                paths.len(),
+                paths.iter().map(|p| p.count).sum::<u128>(),
+                colour
+            );
+        }
+        Some(mode) => return Err(format!("Unknown mode `{}`", mode).into()),
+        None => {
+            println!(
+                "Day07 part1: {}",
+                part1(&graph, &colour).ok_or_else(unknown)?
+            );
+            println!("Day07 part2: {}", part2(&graph, &colour)?);
+        }
+    }
 
     Ok(())
 }
 
-mod part1 {
-    use std::collections::{HashMap, HashSet};
-    type Parents<'a> = Vec<&'a str>;
+/// How many colours can eventually contain a `colour` bag.
+fn part1(graph: &BagGraph, colour: &str) -> Option<usize> {
+    graph.containers_of(colour).map(|bags| bags.len())
+}
+
+/// How many bags a `colour` bag contains.
+fn part2(graph: &BagGraph, colour: &str) -> Result<u128, String> {
+    graph.contents_count(colour)
+}
+
+/// Index of a colour in a [`BagGraph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct BagId(usize);
+
+/// All bag rules, with every colour interned to a [`BagId`].
+/// Edges are stored in both directions along with how many bags go inside.
+#[derive(Debug, Default)]
+struct BagGraph {
+    names: Vec<String>,
+    ids: HashMap<String, BagId>,
+    /// Bags directly inside each bag.
+    children: Vec<Vec<(BagId, usize)>>,
+    /// Bags directly holding each bag.
+    parents: Vec<Vec<(BagId, usize)>>,
+    /// Lines with a rule for each bag.
+    defined_on: Vec<Vec<usize>>,
+    /// The first line mentioning each bag.
+    first_seen: Vec<usize>,
+    /// Bags listed more than once in a rule, with the bag of the rule and its line.
+    repeated: Vec<(BagId, BagId, usize)>,
+}
+
+impl BagGraph {
+    fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut graph = Self::default();
+
+        for (i, line) in s.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
 
-    pub fn solve<'a>(s: &'a str) -> usize {
-        let mut bags = HashMap::<&'a str, Parents<'a>>::new();
+            let (name, children) = parse_bag_line(line).map_err(|err| err.at_line(i + 1))?;
+            let bag = graph.intern(name, i + 1);
+            graph.defined_on[bag.0].push(i + 1);
 
-        for (name, children) in s
-            .trim()
-            .lines()
-            .map(|line| super::parse_bag_line(line).expect("parsing bag line"))
-        {
+            let first_child = graph.children[bag.0].len();
             for child in children {
-                bags.entry(child.name).or_default().push(name);
+                let child_id = graph.intern(child.name, i + 1);
+                if graph.children[bag.0][first_child..]
+                    .iter()
+                    .any(|&(c, _)| c == child_id)
+                {
+                    graph.repeated.push((bag, child_id, i + 1));
+                }
+                graph.children[bag.0].push((child_id, child.count));
+                graph.parents[child_id.0].push((bag, child.count));
             }
         }
 
-        let gold_bag_parents = bags.get("shiny gold").expect("que?! no shiny gold bag");
+        Ok(graph)
+    }
+
+    fn intern(&mut self, name: &str, line: usize) -> BagId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+
+        let id = BagId(self.names.len());
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.children.push(Vec::new());
+        self.parents.push(Vec::new());
+        self.defined_on.push(Vec::new());
+        self.first_seen.push(line);
+        id
+    }
+
+    fn id(&self, colour: &str) -> Option<BagId> {
+        self.ids.get(colour).copied()
+    }
 
-        let mut contains_goldy: Vec<&'a str> = Vec::new();
-        rec_climber(&gold_bag_parents, &bags, &mut contains_goldy);
+    fn name(&self, id: BagId) -> &str {
+        &self.names[id.0]
+    }
 
-        let unique_containers = contains_goldy.into_iter().collect::<HashSet<_>>();
+    fn ids(&self) -> impl Iterator<Item = BagId> {
+        (0..self.names.len()).map(BagId)
+    }
 
-        unique_containers.len()
+    /// Every colour that can eventually hold a `colour` bag, sorted by name.
+    fn containers_of(&self, colour: &str) -> Option<Vec<&str>> {
+        let seen = self.reach(self.id(colour)?, &self.parents);
+        Some(self.sorted_names(|bag| seen[bag.0]))
     }
 
-    /// Used to visit all parents for part1
-    fn rec_climber<'a>(
-        parents: &[&'a str],
-        bags: &HashMap<&'a str, Parents<'a>>,
-        res: &mut Vec<&'a str>,
-    ) {
-        for par_name in parents {
-            res.push(par_name);
-            if let Some(parents) = bags.get(par_name) {
-                rec_climber(&parents, &bags, res);
+    /// Marks every bag reached by following `edges` from `start`, breadth first.
+    /// `start` itself is only marked if it can be reached again.
+    fn reach(&self, start: BagId, edges: &[Vec<(BagId, usize)>]) -> Vec<bool> {
+        let mut seen = vec![false; self.names.len()];
+        let mut queue = VecDeque::from(vec![start]);
+
+        while let Some(bag) = queue.pop_front() {
+            for &(next, _) in &edges[bag.0] {
+                if !seen[next.0] {
+                    seen[next.0] = true;
+                    queue.push_back(next);
+                }
             }
         }
+
+        seen
     }
-}
 
-mod part2 {
+    /// Total number of bags inside a `colour` bag, not counting itself.
+    /// Fails for unknown colours, when a `colour` bag would hold itself
+    /// and when the total does not fit in a `u128`.
+    fn contents_count(&self, colour: &str) -> Result<u128, String> {
+        let bag = self
+            .id(colour)
+            .ok_or_else(|| format!("No rule mentions a `{}` bag", colour))?;
+
+        Ok(self.totals(bag)?[bag.0] - 1)
+    }
+
+    /// The number of bags in each bag inside `bag`, including the bag itself.
+    /// Bags not inside `bag` are left at 0.
+    fn totals(&self, bag: BagId) -> Result<Vec<u128>, String> {
+        let colour = self.name(bag);
+        let walk = self.walk(Some(bag), true);
+        if let Some(cycle) = walk.cycles.first() {
+            return Err(format!(
+                "`{}` bags never run out, {}",
+                colour,
+                Problem::Cycle(self.path_names(cycle))
+            ));
+        }
+
+        // Every bag comes after the bags inside it, so each total is only
+        // computed once and from totals that are already known.
+        let mut totals = vec![0u128; self.names.len()];
+        for &b in &walk.order {
+            totals[b.0] = self.children[b.0]
+                .iter()
+                .try_fold(1u128, |total, &(child, count)| {
+                    (count as u128)
+                        .checked_mul(totals[child.0])
+                        .and_then(|n| total.checked_add(n))
+                })
+                .ok_or_else(|| format!("`{}` bags hold more than {} bags", colour, u128::MAX))?;
+        }
+
+        Ok(totals)
+    }
+
+    /// The colours and counts of the bags directly inside a `colour` bag.
+    fn direct_children(&self, colour: &str) -> Option<Vec<(&str, usize)>> {
+        let children = &self.children[self.id(colour)?.0];
+        Some(
+            children
+                .iter()
+                .map(|&(child, count)| (self.name(child), count))
+                .collect(),
+        )
+    }
+
+    /// Colours that no other bag may contain, sorted by name.
+    fn roots(&self) -> Vec<&str> {
+        self.sorted_names(|bag| self.parents[bag.0].is_empty())
+    }
+
+    /// Colours that contain no other bags, sorted by name.
+    fn leaves(&self) -> Vec<&str> {
+        self.sorted_names(|bag| self.children[bag.0].is_empty())
+    }
+
+    /// Every cycle, undefined bag, bag with several rules and
+    /// bag listed twice in a rule, in that order.
+    fn validate(&self) -> Vec<Problem> {
+        let cycles = self.walk(self.ids(), false).cycles.into_iter();
+        let undefined = self
+            .ids()
+            .filter(|bag| self.defined_on[bag.0].is_empty())
+            .map(|bag| Problem::Undefined {
+                colour: self.name(bag).to_string(),
+                line: self.first_seen[bag.0],
+            });
+        let duplicates = self
+            .ids()
+            .filter(|bag| self.defined_on[bag.0].len() > 1)
+            .map(|bag| Problem::Duplicate {
+                colour: self.name(bag).to_string(),
+                lines: self.defined_on[bag.0].clone(),
+            });
+        let repeated = self
+            .repeated
+            .iter()
+            .map(|&(bag, child, line)| Problem::Repeated {
+                colour: self.name(child).to_string(),
+                container: self.name(bag).to_string(),
+                line,
+            });
+
+        cycles
+            .map(|cycle| Problem::Cycle(self.path_names(&cycle)))
+            .chain(undefined)
+            .chain(duplicates)
+            .chain(repeated)
+            .collect()
+    }
+
+    /// Walks depth first from each of `starts`, through the bags inside them.
+    /// Stops at the first cycle if `first_only` is set.
+    ///
+    /// Uses its own stack, as rule sets can be deeper than the call stack.
+    fn walk(&self, starts: impl IntoIterator<Item = BagId>, first_only: bool) -> Walk {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum State {
+            New,
+            Open,
+            Done,
+        }
+
+        let mut state = vec![State::New; self.names.len()];
+        let mut walk = Walk::default();
+
+        for start in starts {
+            if state[start.0] != State::New {
+                continue;
+            }
+
+            // The open bags, with the index of their next child to visit.
+            let mut stack = vec![(start, 0)];
+            state[start.0] = State::Open;
 
-    use std::collections::HashMap;
+            while let Some(&(bag, next)) = stack.last() {
+                let child = match self.children[bag.0].get(next) {
+                    Some(&(child, _)) => child,
+                    None => {
+                        state[bag.0] = State::Done;
+                        walk.order.push(bag);
+                        stack.pop();
+                        continue;
+                    }
+                };
 
-    type Children<'a> = Vec<super::ChildBag<'a>>;
-    type Bags<'a> = HashMap<&'a str, Children<'a>>;
+                if let Some(top) = stack.last_mut() {
+                    top.1 += 1;
+                }
 
-    pub fn solve(s: &str) -> usize {
-        let mut bags = Bags::new();
+                match state[child.0] {
+                    State::New => {
+                        state[child.0] = State::Open;
+                        stack.push((child, 0));
+                    }
+                    State::Open => {
+                        let from = stack
+                            .iter()
+                            .position(|&(b, _)| b == child)
+                            .expect("open bag");
+                        let mut cycle = stack[from..].iter().map(|&(b, _)| b).collect::<Vec<_>>();
+                        cycle.push(child);
+                        walk.cycles.push(cycle);
 
-        for (name, children) in s
-            .trim()
-            .lines()
-            .map(|line| super::parse_bag_line(line).expect("parsing bag line"))
-        {
-            bags.insert(name, children.collect());
+                        if first_only {
+                            return walk;
+                        }
+                    }
+                    State::Done => (),
+                }
+            }
         }
 
-        count_children("shiny gold", &bags) - 1 // Subtract the shiny gold bag
+        walk
     }
 
-    fn count_children(bag: &'_ str, bags: &Bags<'_>) -> usize {
-        let mut res = 1; // count ourselves.
-        if let Some(children) = bags.get(bag) {
-            for child in children {
-                res += child.count * count_children(child.name, &bags);
+    fn path_names(&self, path: &[BagId]) -> Vec<String> {
+        path.iter().map(|&bag| self.name(bag).to_string()).collect()
+    }
+
+    fn sorted_names(&self, keep: impl Fn(BagId) -> bool) -> Vec<&str> {
+        let mut names = self
+            .ids()
+            .filter(|&bag| keep(bag))
+            .map(|bag| self.name(bag))
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// The result of [`BagGraph::walk`].
+#[derive(Debug, Default)]
+struct Walk {
+    /// Every bag reached, each after all the bags inside it.
+    order: Vec<BagId>,
+    /// Each cycle starts and ends with the same bag, e.g. `[a, b, a]`.
+    cycles: Vec<Vec<BagId>>,
+}
+
+/// Something wrong with a set of rules, found by [`BagGraph::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    /// Bags that end up inside themselves, from the outermost bag and back.
+    Cycle(Vec<String>),
+    /// A colour that is inside other bags but has no rule of its own.
+    Undefined { colour: String, line: usize },
+    /// A colour with more than one rule.
+    Duplicate { colour: String, lines: Vec<usize> },
+    /// A colour listed more than once inside the same rule.
+    Repeated {
+        colour: String,
+        container: String,
+        line: usize,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Cycle(path) => write!(f, "cycle {}", path.join(" -> ")),
+            Self::Undefined { colour, line } => {
+                write!(f, "line {}: `{}` bags are never defined", line, colour)
             }
+            Self::Duplicate { colour, lines } => write!(
+                f,
+                "`{}` bags are defined on lines {}",
+                colour,
+                lines
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::Repeated {
+                colour,
+                container,
+                line,
+            } => write!(
+                f,
+                "line {}: `{}` bags are listed more than once inside `{}` bags",
+                line, colour, container
+            ),
         }
-        res
     }
 }
 
-fn parse_bag_line<'a>(s: &'a str) -> crate::Result<(&'a str, impl Iterator<Item = ChildBag<'a>>)> {
-    let mut split = s.trim().split("bags contain");
-    let name = split
-        .next()
-        .ok_or_else(|| format!("initial part missing from bag line `{}`", s))?
-        .trim();
+/// Parses a rule on the form
+/// `<colour> bags contain <count> <colour> bag[s], ... .`
+/// or `<colour> bags contain no other bags.`
+///
+/// A count of 1 must be followed by `bag` and any other count by `bags`.
+/// Children with a count of 0 hold nothing and are left out,
+/// so `0 other bags` reads the same as `no other bags`.
+fn parse_bag_line(s: &str) -> Result<(&str, Vec<ChildBag<'_>>), ParseError> {
+    // Byte range of `sub` within `s`, which it must be a slice of.
+    let span = |sub: &str| {
+        let start = sub.as_ptr() as usize - s.as_ptr() as usize;
+        start..start + sub.len()
+    };
 
-    let contains_s = split
-        .next()
-        .ok_or_else(|| format!("contains part missing from bag line `{}`", s))?
-        .trim();
+    let line = s.trim();
+    let body = line.strip_suffix('.').ok_or_else(|| {
+        ParseError::new(span(&line[line.len()..]), "expected `.` ending the rule")
+    })?;
 
-    let contains = contains_s
-        .split(',')
-        .filter(|s| *s != "no other bags.")
-        .map(|child| child.trim())
-        .map(|child| {
-            let (count_s, rest) = child.split_at(1);
-            let count = count_s
-                .parse::<usize>()
-                .map_err(|_| format!("Invalid count in `{}`", child))
-                .expect("Parsing child count");
-
-            let (name, _) = rest.split_at(rest.find("bag").expect("finding `bag`"));
-            ChildBag {
-                name: name.trim(),
-                count,
+    let (name, contents) = body.split_once(" bags contain ").ok_or_else(|| {
+        ParseError::new(span(body), "expected `<colour> bags contain <contents>`")
+    })?;
+
+    if name.trim().is_empty() {
+        return Err(ParseError::new(span(name), "expected colour"));
+    }
+
+    if contents == "no other bags" {
+        return Ok((name, Vec::new()));
+    }
+
+    let mut children = Vec::new();
+    for item in contents.split(", ") {
+        let (count_s, rest) = item.split_once(' ').unwrap_or((item, ""));
+        let (colour, noun) = rest.rsplit_once(' ').unwrap_or(("", rest));
+
+        let count = match count_s.parse::<usize>() {
+            Ok(count) => count,
+            Err(_) if !count_s.is_empty() && count_s.bytes().all(|b| b.is_ascii_digit()) => {
+                return Err(ParseError::new(
+                    span(count_s),
+                    format!("count `{}` is too large", count_s),
+                ))
             }
-        });
+            Err(_) => {
+                return Err(ParseError::new(
+                    span(count_s),
+                    format!("expected count, found `{}`", count_s),
+                ))
+            }
+        };
+
+        if colour.trim().is_empty() {
+            return Err(ParseError::new(
+                span(item),
+                format!("expected `<count> <colour> bags`, found `{}`", item),
+            ));
+        }
+
+        match (count, noun) {
+            (1, "bag") => (),
+            (1, "bags") => {
+                return Err(ParseError::new(
+                    span(noun),
+                    "expected `bag` after a count of 1",
+                ))
+            }
+            (_, "bags") => (),
+            (n, "bag") => {
+                return Err(ParseError::new(
+                    span(noun),
+                    format!("expected `bags` after a count of {}", n),
+                ))
+            }
+            (_, noun) => {
+                return Err(ParseError::new(
+                    span(noun),
+                    format!("expected `bag` or `bags`, found `{}`", noun),
+                ))
+            }
+        }
+
+        if count > 0 {
+            children.push(ChildBag {
+                name: colour,
+                count,
+            });
+        }
+    }
+
+    Ok((name, children))
+}
+
+/// A malformed bag rule.
+/// `span` is the byte range of the offending text within its line,
+/// `line` is 1-indexed and 0 until known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    span: Range<usize>,
+    msg: String,
+}
+
+impl ParseError {
+    fn new(span: Range<usize>, msg: impl Into<String>) -> Self {
+        Self {
+            line: 0,
+            span,
+            msg: msg.into(),
+        }
+    }
+
+    fn at_line(self, line: usize) -> Self {
+        Self { line, ..self }
+    }
+}
 
-    Ok((name, contains))
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, columns {}-{}: {}",
+            self.line,
+            self.span.start + 1,
+            self.span.end.max(self.span.start + 1),
+            self.msg
+        )
+    }
 }
 
+impl std::error::Error for ParseError {}
+
 #[derive(Debug)]
 struct ChildBag<'a> {
     count: usize,
     name: &'a str,
 }
 
+/// Shows where the answers come from, as indented trees.
+mod explain {
+    use super::{BagGraph, BagId};
+    use std::fmt::Write;
+
+    /// A way to reach a bag from an outer bag, and how many of the
+    /// inner bag it makes up, i.e. the counts along it multiplied.
+    /// `counts` has how many of each bag the bag before it holds,
+    /// and 1 for the outermost bag.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Path {
+        pub bags: Vec<BagId>,
+        pub counts: Vec<usize>,
+        pub count: u128,
+    }
+
+    /// Every way a `from` bag holds `to` bags, depth first in rule order.
+    pub fn paths(graph: &BagGraph, from: BagId, to: BagId) -> Result<Vec<Path>, String> {
+        // Fails on cycles and overflow, which would also break the paths below.
+        graph.totals(from)?;
+
+        let mut leads_to = graph.reach(to, &graph.parents);
+        leads_to[to.0] = true;
+
+        let mut paths = Vec::new();
+        let mut stack = vec![Path {
+            bags: vec![from],
+            counts: vec![1],
+            count: 1,
+        }];
+
+        while let Some(path) = stack.pop() {
+            let bag = *path.bags.last().expect("path is never empty");
+            if bag == to && path.bags.len() > 1 {
+                paths.push(path);
+                continue;
+            }
+
+            // Pushed in reverse, so they are popped in rule order.
+            for &(child, count) in graph.children[bag.0].iter().rev() {
+                if leads_to[child.0] {
+                    let mut bags = path.bags.clone();
+                    bags.push(child);
+                    let mut counts = path.counts.clone();
+                    counts.push(count);
+                    stack.push(Path {
+                        bags,
+                        counts,
+                        count: path.count * count as u128,
+                    });
+                }
+            }
+        }
+
+        Ok(paths)
+    }
+
+    /// Draws `paths` as a tree, merging their shared beginnings.
+    /// Each line shows how many of the bag there are per bag on the line above,
+    /// and in brackets how many there are per outermost bag.
+    pub fn path_tree(graph: &BagGraph, paths: &[Path]) -> String {
+        let mut out = String::new();
+        let mut prev: Option<&Path> = None;
+
+        for path in paths {
+            let shared = prev.map_or(0, |prev| {
+                prev.bags
+                    .iter()
+                    .zip(&prev.counts)
+                    .zip(path.bags.iter().zip(&path.counts))
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            });
+
+            let mut total = 1;
+            for (depth, (&bag, &count)) in path.bags.iter().zip(&path.counts).enumerate() {
+                total *= count as u128;
+
+                if depth < shared {
+                    continue;
+                }
+
+                match depth {
+                    0 => writeln!(out, "{}", graph.name(bag)),
+                    _ => writeln!(
+                        out,
+                        "{:indent$}{} {} ({})",
+                        "",
+                        count,
+                        graph.name(bag),
+                        total,
+                        indent = depth * 2
+                    ),
+                }
+                .expect("writing tree");
+            }
+
+            prev = Some(path);
+        }
+
+        out
+    }
+
+    /// Draws how the number of bags inside `bag` adds up, one line per
+    /// bag inside it: `<count> <colour>: <count> × (1 + <inside>) = <bags>`.
+    pub fn breakdown(graph: &BagGraph, bag: BagId) -> Result<String, String> {
+        let totals = graph.totals(bag)?;
+        let mut out = format!("{}: {} bags inside\n", graph.name(bag), totals[bag.0] - 1);
+
+        // The bags to draw and how deep, last one first.
+        let mut stack = graph.children[bag.0]
+            .iter()
+            .rev()
+            .map(|&edge| (edge, 1))
+            .collect::<Vec<_>>();
+
+        while let Some(((child, count), depth)) = stack.pop() {
+            writeln!(
+                out,
+                "{:indent$}{} {}: {} × (1 + {}) = {}",
+                "",
+                count,
+                graph.name(child),
+                count,
+                totals[child.0] - 1,
+                count as u128 * totals[child.0],
+                indent = depth * 2
+            )
+            .expect("writing breakdown");
+
+            stack.extend(
+                graph.children[child.0]
+                    .iter()
+                    .rev()
+                    .map(|&edge| (edge, depth + 1)),
+            );
+        }
+
+        Ok(out)
+    }
+
+    /// The bag directly inside `bag` that brings the most bags with it,
+    /// counting itself and everything inside it, and how many.
+    pub fn biggest_contributor(
+        graph: &BagGraph,
+        bag: BagId,
+    ) -> Result<Option<(BagId, u128)>, String> {
+        let totals = graph.totals(bag)?;
+        Ok(graph.children[bag.0]
+            .iter()
+            .map(|&(child, count)| (child, count as u128 * totals[child.0]))
+            .max_by_key(|&(_, bags)| bags))
+    }
+}
+
+/// Draws the rules as Graphviz DOT or Mermaid flowcharts, with an edge
+/// from each bag to every bag directly inside it labelled by the count.
+mod export {
+    use super::{BagGraph, BagId};
+    use std::{fmt::Write, str::FromStr};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Format {
+        Dot,
+        Mermaid,
+    }
+
+    /// Which bags to draw, relative to the highlighted bag.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Scope {
+        All,
+        /// The bag and everything it eventually holds.
+        Inside,
+        /// The bag and everything that eventually holds it.
+        Holders,
+    }
+
+    impl FromStr for Scope {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "all" => Ok(Self::All),
+                "inside" => Ok(Self::Inside),
+                "holders" => Ok(Self::Holders),
+                _ => Err(format!(
+                    "Unknown scope `{}`, expected all, inside or holders",
+                    s
+                )),
+            }
+        }
+    }
+
+    /// Draws the bags in `scope` of `highlight`, which is drawn in gold.
+    /// Fails if `highlight` is `None` for any scope but [`Scope::All`].
+    pub fn export(
+        graph: &BagGraph,
+        format: Format,
+        scope: Scope,
+        highlight: Option<BagId>,
+    ) -> Result<String, String> {
+        let mut keep = match (scope, highlight) {
+            (Scope::Inside, Some(bag)) => graph.reach(bag, &graph.children),
+            (Scope::Holders, Some(bag)) => graph.reach(bag, &graph.parents),
+            (Scope::All, _) => vec![true; graph.names.len()],
+            (_, None) => return Err(format!("{:?} scope needs a bag", scope)),
+        };
+        if let Some(bag) = highlight {
+            keep[bag.0] = true;
+        }
+
+        let bags = graph.ids().filter(|bag| keep[bag.0]).collect::<Vec<_>>();
+        let edges = bags.iter().flat_map(|&bag| {
+            graph.children[bag.0]
+                .iter()
+                .filter(|(child, _)| keep[child.0])
+                .map(move |&(child, count)| (bag, child, count))
+        });
+
+        let mut out = String::new();
+        match format {
+            Format::Dot => {
+                out.push_str("digraph bags {\n");
+                for &bag in &bags {
+                    if Some(bag) == highlight {
+                        writeln!(
+                            out,
+                            "    {} [style=filled, fillcolor=gold];",
+                            dot_id(graph.name(bag))
+                        )
+                    } else {
+                        writeln!(out, "    {};", dot_id(graph.name(bag)))
+                    }
+                    .expect("writing export");
+                }
+                for (bag, child, count) in edges {
+                    writeln!(
+                        out,
+                        "    {} -> {} [label=\"{}\"];",
+                        dot_id(graph.name(bag)),
+                        dot_id(graph.name(child)),
+                        count
+                    )
+                    .expect("writing export");
+                }
+                out.push_str("}\n");
+            }
+            Format::Mermaid => {
+                out.push_str("flowchart TD\n");
+                for &bag in &bags {
+                    let name = graph.name(bag).replace('"', "#quot;");
+                    writeln!(out, "    b{}[\"{}\"]", bag.0, name).expect("writing export");
+                }
+                for (bag, child, count) in edges {
+                    writeln!(out, "    b{} -->|{}| b{}", bag.0, count, child.0)
+                        .expect("writing export");
+                }
+                if let Some(bag) = highlight {
+                    writeln!(out, "    style b{} fill:gold", bag.0).expect("writing export");
+                }
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn dot_id(name: &str) -> String {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Compares the memoised counting and breadth first container search with
+/// the original recursive solutions, on generated rule sets.
+/// Run with `cargo run --release 7 bench`.
+mod bench {
+    use super::{BagGraph, BagId};
+    use std::{collections::HashSet, time::Instant};
+
+    pub fn run() {
+        // Every tier holds the next two tiers, so the number of paths
+        // to the bottom grows like the fibonacci numbers.
+        for &depth in &[20, 25, 30] {
+            let graph = BagGraph::parse(&ladder(depth, 1)).expect("parsing ladder");
+            compare(&format!("ladder of {} tiers", depth), &graph);
+        }
+
+        // Too deep for any recursion to survive.
+        let depth = 200_000;
+        let graph = BagGraph::parse(&chain(depth)).expect("parsing chain");
+        let start = Instant::now();
+        let count = graph.contents_count("tier 0");
+        let containers = graph.containers_of(&tier(depth)).map(|c| c.len());
+        println!(
+            "chain of {} tiers:  memoised + bfs {:>10?}  -> {:?}, {:?}",
+            depth,
+            start.elapsed(),
+            count,
+            containers
+        );
+
+        let graph = BagGraph::parse(&ladder(200, 1_000)).expect("parsing ladder");
+        println!(
+            "ladder of 200 tiers, 1000 each: {:?}",
+            graph.contents_count("tier 0")
+        );
+    }
+
+    fn compare(name: &str, graph: &BagGraph) {
+        let top = graph.id("tier 0").expect("top tier");
+        let bottom = graph.leaves()[0];
+
+        let start = Instant::now();
+        let naive = (count_naive(graph, top) - 1, climb_naive(graph, bottom));
+        println!(
+            "{}:  recursive {:>12?}  -> {:?}",
+            name,
+            start.elapsed(),
+            naive
+        );
+
+        let start = Instant::now();
+        let fast = (
+            graph.contents_count("tier 0").expect("counting"),
+            graph.containers_of(bottom).expect("climbing").len(),
+        );
+        println!(
+            "{}:  memoised  {:>12?}  -> {:?}",
+            name,
+            start.elapsed(),
+            fast
+        );
+    }
+
+    pub fn tier(i: usize) -> String {
+        format!("tier {}", i)
+    }
+
+    fn plural(count: usize) -> &'static str {
+        if count == 1 {
+            "bag"
+        } else {
+            "bags"
+        }
+    }
+
+    /// Rules where each tier holds `count` bags of the next two tiers.
+    pub fn ladder(depth: usize, count: usize) -> String {
+        let mut rules = String::new();
+        for i in 0..depth {
+            let next = (i + 1..depth.min(i + 3))
+                .map(|j| format!("{} {} {}", count, tier(j), plural(count)))
+                .collect::<Vec<_>>();
+            let contents = match next.len() {
+                0 => "no other bags".to_string(),
+                _ => next.join(", "),
+            };
+            rules.push_str(&format!("{} bags contain {}.\n", tier(i), contents));
+        }
+        rules
+    }
+
+    /// Rules where each tier holds a single bag of the next tier.
+    pub fn chain(depth: usize) -> String {
+        let mut rules = String::new();
+        for i in 0..depth {
+            rules.push_str(&format!(
+                "{} bags contain 1 {} bag.\n",
+                tier(i),
+                tier(i + 1)
+            ));
+        }
+        rules.push_str(&format!("{} bags contain no other bags.\n", tier(depth)));
+        rules
+    }
+
+    /// The original count, recursing into every bag on every path.
+    pub fn count_naive(graph: &BagGraph, bag: BagId) -> u128 {
+        1 + graph.children[bag.0]
+            .iter()
+            .map(|&(child, count)| count as u128 * count_naive(graph, child))
+            .sum::<u128>()
+    }
+
+    /// The original search, recording every container on every path
+    /// before removing duplicates.
+    pub fn climb_naive(graph: &BagGraph, colour: &str) -> usize {
+        fn climb(graph: &BagGraph, bag: BagId, res: &mut Vec<BagId>) {
+            for &(parent, _) in &graph.parents[bag.0] {
+                res.push(parent);
+                climb(graph, parent, res);
+            }
+        }
+
+        let mut res = Vec::new();
+        climb(graph, graph.id(colour).expect("known colour"), &mut res);
+        res.into_iter().collect::<HashSet<_>>().len()
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{parse_bag_line, BagGraph};
 
-    #[test]
-    fn part1_example() {
-        let input = r#"
+    static EXAMPLE: &str = r#"
 light red bags contain 1 bright white bag, 2 muted yellow bags.
 dark orange bags contain 3 bright white bags, 4 muted yellow bags.
 bright white bags contain 1 shiny gold bag.
@@ -181,16 +1073,311 @@ dark olive bags contain 3 faded blue bags, 4 dotted black bags.
 vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.
+"#;
+
+    fn solve_part1(s: &str) -> usize {
+        let graph = BagGraph::parse(s).expect("parsing rules");
+        super::part1(&graph, "shiny gold").expect("shiny gold bag")
+    }
+
+    fn solve_part2(s: &str) -> u128 {
+        let graph = BagGraph::parse(s).expect("parsing rules");
+        super::part2(&graph, "shiny gold").expect("shiny gold bag")
+    }
+
+    #[test]
+    fn part1_example() {
+        assert_eq!(4, solve_part1(EXAMPLE));
+    }
+
+    #[test]
+    fn queries() {
+        let graph = BagGraph::parse(EXAMPLE).expect("parsing rules");
+
+        assert_eq!(
+            graph.containers_of("muted yellow"),
+            Some(vec!["dark orange", "light red"])
+        );
+        assert_eq!(graph.containers_of("light red"), Some(vec![]));
+        assert_eq!(graph.containers_of("plaid purple"), None);
+
+        assert_eq!(graph.contents_count("dark olive"), Ok(7));
+        assert_eq!(graph.contents_count("faded blue"), Ok(0));
+        assert_eq!(
+            graph.direct_children("muted yellow"),
+            Some(vec![("shiny gold", 2), ("faded blue", 9)])
+        );
+
+        assert_eq!(graph.roots(), ["dark orange", "light red"]);
+        assert_eq!(graph.leaves(), ["dotted black", "faded blue"]);
+    }
+
+    #[test]
+    fn parse_counts_and_names() {
+        let (name, children) =
+            parse_bag_line("baggy tan bags contain 12 faded blue bags, 1 bag gray bag.")
+                .expect("parsing rule");
+        assert_eq!(name, "baggy tan");
+        assert_eq!(
+            children
+                .iter()
+                .map(|c| (c.count, c.name))
+                .collect::<Vec<_>>(),
+            [(12, "faded blue"), (1, "bag gray")]
+        );
+
+        let (_, children) =
+            parse_bag_line("dark red bags contain 0 other bags.").expect("parsing rule");
+        assert!(children.is_empty());
+    }
+
+    #[test]
+    fn parse_errors_report_span() {
+        let err = |s| parse_bag_line(s).expect_err(s);
+        let span = |s| err(s).span;
+
+        assert_eq!(span("dark red bags contain 2 dark orange bag."), 36..39);
+        assert_eq!(span("dark red bags contain 1 dark orange bags."), 36..40);
+        assert_eq!(span("dark red bags contain x dark orange bags."), 22..23);
+        assert_eq!(span("dark red bags contain 2 bags."), 22..28);
+        assert_eq!(span("dark red bags contain 2 dark orange boxes."), 36..41);
+        assert_eq!(span("dark red bags contain no other bags"), 35..35);
+        assert_eq!(span("dark red bags hold no other bags."), 0..32);
+        assert_eq!(
+            span("dark red bags contain 99999999999999999999999 dark orange bags."),
+            22..45
+        );
+
+        let err = BagGraph::parse(
+            "faded blue bags contain no other bags.\n\nbright white bags contain 2 shiny gold bag.",
+        )
+        .expect_err("parsing rules");
+        assert_eq!(
+            err.to_string(),
+            "line 3, columns 40-42: expected `bags` after a count of 2"
+        );
+    }
+
+    #[test]
+    fn validate() {
+        use super::Problem;
+
+        let graph = BagGraph::parse(
+            "dark red bags contain 2 dark orange bags, 1 faded blue bag.
+dark orange bags contain 3 dark yellow bags.
+dark yellow bags contain 1 dark red bag.
+faded blue bags contain 1 faded blue bag, 1 posh aqua bag.
+dark orange bags contain no other bags.
+plain tan bags contain 1 dark yellow bag, 2 dark yellow bags.
+",
+        )
+        .expect("parsing rules");
+
+        let problems = graph.validate();
+        assert_eq!(
+            problems,
+            vec![
+                Problem::Cycle(vec![
+                    "dark red".to_string(),
+                    "dark orange".to_string(),
+                    "dark yellow".to_string(),
+                    "dark red".to_string(),
+                ]),
+                Problem::Cycle(vec!["faded blue".to_string(), "faded blue".to_string()]),
+                Problem::Undefined {
+                    colour: "posh aqua".to_string(),
+                    line: 4
+                },
+                Problem::Duplicate {
+                    colour: "dark orange".to_string(),
+                    lines: vec![2, 5]
+                },
+                Problem::Repeated {
+                    colour: "dark yellow".to_string(),
+                    container: "plain tan".to_string(),
+                    line: 6
+                },
+            ]
+        );
+        assert_eq!(
+            problems[0].to_string(),
+            "cycle dark red -> dark orange -> dark yellow -> dark red"
+        );
+        assert_eq!(
+            problems[2].to_string(),
+            "line 4: `posh aqua` bags are never defined"
+        );
+        assert_eq!(
+            problems[3].to_string(),
+            "`dark orange` bags are defined on lines 2, 5"
+        );
+        assert_eq!(
+            problems[4].to_string(),
+            "line 6: `dark yellow` bags are listed more than once inside `plain tan` bags"
+        );
+
+        assert_eq!(
+            graph.contents_count("dark yellow"),
+            Err("`dark yellow` bags never run out, cycle dark yellow -> dark red -> dark orange -> dark yellow".to_string())
+        );
+        assert_eq!(graph.contents_count("posh aqua"), Ok(0));
+        assert!(BagGraph::parse(EXAMPLE)
+            .expect("parsing rules")
+            .validate()
+            .is_empty());
+    }
+
+    #[test]
+    fn deep_and_large_counts() {
+        use super::bench;
+
+        let graph = BagGraph::parse(&bench::ladder(15, 2)).expect("parsing ladder");
+        let top = graph.id("tier 0").expect("top tier");
+        assert_eq!(
+            graph.contents_count("tier 0"),
+            Ok(bench::count_naive(&graph, top) - 1)
+        );
+        assert_eq!(
+            graph.containers_of("tier 14").map(|c| c.len()),
+            Some(bench::climb_naive(&graph, "tier 14"))
+        );
+
+        let graph = BagGraph::parse(&bench::chain(100_000)).expect("parsing chain");
+        assert_eq!(graph.contents_count("tier 0"), Ok(100_000));
+        assert_eq!(
+            graph.containers_of("tier 100000").map(|c| c.len()),
+            Some(100_000)
+        );
+
+        let graph = BagGraph::parse(&bench::ladder(100, 1_000)).expect("parsing ladder");
+        assert_eq!(
+            graph.contents_count("tier 0"),
+            Err(format!("`tier 0` bags hold more than {} bags", u128::MAX))
+        );
+        assert!(graph.contents_count("tier 90").is_ok());
+    }
+
+    #[test]
+    fn export() {
+        use super::export::{export, Format, Scope};
+
+        let graph = BagGraph::parse(EXAMPLE).expect("parsing rules");
+        let gold = graph.id("shiny gold");
+
+        let dot = export(&graph, Format::Dot, Scope::Inside, gold).expect("exporting");
+        assert_eq!(
+            dot,
+            r#"digraph bags {
+    "shiny gold" [style=filled, fillcolor=gold];
+    "faded blue";
+    "dark olive";
+    "vibrant plum";
+    "dotted black";
+    "shiny gold" -> "dark olive" [label="1"];
+    "shiny gold" -> "vibrant plum" [label="2"];
+    "dark olive" -> "faded blue" [label="3"];
+    "dark olive" -> "dotted black" [label="4"];
+    "vibrant plum" -> "faded blue" [label="5"];
+    "vibrant plum" -> "dotted black" [label="6"];
+}
 "#
-        .trim();
+        );
+
+        let mermaid = export(
+            &graph,
+            Format::Mermaid,
+            Scope::Holders,
+            graph.id("bright white"),
+        )
+        .expect("exporting");
+        assert_eq!(
+            mermaid,
+            r#"flowchart TD
+    b0["light red"]
+    b1["bright white"]
+    b3["dark orange"]
+    b0 -->|1| b1
+    b3 -->|3| b1
+    style b1 fill:gold
+"#
+        );
+
+        let all = export(&graph, Format::Dot, Scope::All, None).expect("exporting");
+        assert_eq!(all.matches(" -> ").count(), 13);
+        assert_eq!(all.lines().count(), 2 + 9 + 13);
+        assert_eq!(
+            export(&graph, Format::Mermaid, Scope::Holders, None),
+            Err("Holders scope needs a bag".to_string())
+        );
+    }
+
+    #[test]
+    fn explain() {
+        use super::explain;
+
+        let graph = BagGraph::parse(EXAMPLE).expect("parsing rules");
+        let id = |name| graph.id(name).expect("known colour");
+
+        let paths = explain::paths(&graph, id("light red"), id("shiny gold")).expect("paths");
+        assert_eq!(paths.iter().map(|p| p.count).collect::<Vec<_>>(), [1, 4]);
+        assert_eq!(
+            explain::path_tree(&graph, &paths),
+            "light red
+  1 bright white (1)
+    1 shiny gold (1)
+  2 muted yellow (2)
+    2 shiny gold (4)
+"
+        );
+        assert_eq!(
+            explain::paths(&graph, id("shiny gold"), id("light red")),
+            Ok(vec![])
+        );
+
+        let repeated =
+            BagGraph::parse("a bags contain 1 b bag, 2 b bags.\nb bags contain no other bags.")
+                .expect("parsing rules");
+        let paths = explain::paths(
+            &repeated,
+            repeated.id("a").expect("known colour"),
+            repeated.id("b").expect("known colour"),
+        )
+        .expect("paths");
+        assert_eq!(paths.iter().map(|p| p.count).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(
+            explain::path_tree(&repeated, &paths),
+            "a
+  1 b (1)
+  2 b (2)
+"
+        );
 
-        assert_eq!(4, super::part1::solve(input));
+        assert_eq!(
+            explain::breakdown(&graph, id("shiny gold")),
+            Ok("shiny gold: 32 bags inside
+  1 dark olive: 1 × (1 + 7) = 8
+    3 faded blue: 3 × (1 + 0) = 3
+    4 dotted black: 4 × (1 + 0) = 4
+  2 vibrant plum: 2 × (1 + 11) = 24
+    5 faded blue: 5 × (1 + 0) = 5
+    6 dotted black: 6 × (1 + 0) = 6
+"
+            .to_string())
+        );
+        assert_eq!(
+            explain::biggest_contributor(&graph, id("shiny gold")),
+            Ok(Some((id("vibrant plum"), 24)))
+        );
+        assert_eq!(
+            explain::biggest_contributor(&graph, id("faded blue")),
+            Ok(None)
+        );
     }
 
     #[test]
     fn part1() {
         let input = crate::read_input("day07.txt").expect("reading input");
-        assert_eq!(316, super::part1::solve(&input));
+        assert_eq!(316, solve_part1(&input));
     }
 
     #[test]
@@ -204,7 +1391,7 @@ dark olive bags contain 3 faded blue bags, 4 dotted black bags.
 "#
         .trim();
 
-        assert_eq!(32, super::part2::solve(input));
+        assert_eq!(32, solve_part2(input));
     }
 
     #[test]
@@ -220,12 +1407,12 @@ dark violet bags contain no other bags.
 "#
         .trim();
 
-        assert_eq!(126, super::part2::solve(input));
+        assert_eq!(126, solve_part2(input));
     }
 
     #[test]
     fn part2() {
         let input = crate::read_input("day07.txt").expect("reading input");
-        assert_eq!(11310, super::part2::solve(&input));
+        assert_eq!(11310, solve_part2(&input));
     }
 }
@@ -119,55 +119,77 @@
 //! Fix the program so that it terminates normally by changing exactly one jmp
 //! (to nop) or nop (to jmp). What is the value of the accumulator after the program terminates?
 
-use std::{collections::HashSet, iter::FromIterator, str::FromStr};
+use std::{collections::HashSet, convert::TryFrom, fmt, iter::FromIterator, str::FromStr};
 
+/// Run with `cargo run 8 [trace [text|json|annotate] | back [steps]]`
+/// to see how part 1 ends up in its loop, or with
+/// `cargo run 8 disasm [indices] [targets] [file]` to list a program
+/// and `cargo run 8 repair` to show the instruction part 2 flips.
 pub fn solve() -> crate::Result<()> {
     let input = crate::read_input("day08.txt")?;
-    println!("Day08 part1: {}", part1(&input));
-    println!("Day08 part2: {}", part2(&input));
+
+    match std::env::args().nth(2).as_deref() {
+        Some("trace") => {
+            let ops = asm::assemble(&input)?;
+            let trace = trace::record(&ops);
+            match std::env::args().nth(3).as_deref() {
+                None | Some("text") => print!("{}", trace.to_text()),
+                Some("json") => println!("{}", trace.to_json()),
+                Some("annotate") => print!("{}", trace.annotate(&ops)),
+                Some(format) => return Err(format!("Unknown trace format `{}`", format).into()),
+            }
+        }
+        Some("back") => {
+            let steps = match std::env::args().nth(3) {
+                Some(n) => n
+                    .parse()
+                    .map_err(|_| format!("Invalid step count `{}`", n))?,
+                None => 10,
+            };
+            let trace = trace::record(&asm::assemble(&input)?);
+            print!("{}", trace.explain_end(steps));
+            if let Some((ptr, acc)) = trace.rewind(steps) {
+                println!("{} steps before halting: ptr {}, acc {}", steps, ptr, acc);
+            }
+        }
+        Some("repair") => match repair::repair(&asm::assemble(&input)?) {
+            Ok(fix) => println!("{}", fix),
+            Err(err) => return Err(err.into()),
+        },
+        Some("disasm") => {
+            let mut listing = asm::Listing::default();
+            let mut source = input;
+            for arg in std::env::args().skip(3) {
+                match arg.as_str() {
+                    "indices" => listing.indices = true,
+                    "targets" => listing.targets = true,
+                    path => source = std::fs::read_to_string(path)?,
+                }
+            }
+            print!("{}", asm::disassemble(&asm::assemble(&source)?, listing));
+        }
+        _ => {
+            println!("Day08 part1: {}", part1(&input)?);
+            println!("Day08 part2: {}", part2(&input)?);
+        }
+    }
 
     Ok(())
 }
 
-fn part1(s: &str) -> i64 {
-    let mut program = s
-        .lines()
-        .map(|line| line.parse::<Op>().expect("Parsing Op"))
-        .collect::<Program>();
+fn part1(s: &str) -> crate::Result<i64> {
+    let mut program = asm::assemble(s)?.into_iter().collect::<Program>();
 
-    if let ProgramResult::Looped(n) = program.run() {
-        n
-    } else {
-        panic!("Part 1 did not loop")
+    match program.run() {
+        ProgramResult::Looped(n) => Ok(n),
+        result => Err(format!("The program halted with {:?} instead of looping", result).into()),
     }
 }
 
-fn part2(s: &str) -> i64 {
-    let mut program = s
-        .lines()
-        .map(|line| line.parse::<Op>().expect("Parsing Op"))
-        .collect::<Program>();
-
-    // Brute for this by simply trying to swap all Jmps and Nops until it exits :p
-
-    for i in 0..program.instructions.len() {
-        program.instructions.get_mut(i).unwrap().swap();
-
-        match program.run() {
-            ProgramResult::Terminated(n) => {
-                return n;
-            }
-            ProgramResult::OutOfBounds => {
-                println!("OUT OF BOUNDS");
-            }
-            _ => (),
-        }
-
-        program.reset();
-        program.instructions.get_mut(i).unwrap().swap();
-    }
-
-    panic!("Failed to find solution for part2")
+/// Finds the one `jmp` or `nop` that, flipped, makes the program terminate.
+fn part2(s: &str) -> crate::Result<i64> {
+    let ops = asm::assemble(s)?;
+    Ok(repair::repair(&ops)?.acc)
 }
 
 struct Program {
@@ -175,6 +197,8 @@ struct Program {
     ptr: i64,
     instructions: Vec<Op>,
     visited: HashSet<usize>,
+    /// Only recorded when set.
+    trace: Option<trace::Trace>,
 }
 
 impl FromIterator<Op> for Program {
@@ -184,12 +208,13 @@ impl FromIterator<Op> for Program {
             ptr: 0,
             instructions: iter.into_iter().collect(),
             visited: Default::default(),
+            trace: None,
         }
     }
 }
 
 /// Used in Part2 to distinguish Program stop due to Re visit or proper exit.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ProgramResult {
     Looped(i64),
     Terminated(i64),
@@ -201,72 +226,974 @@ impl Program {
         self.acc = 0;
         self.ptr = 0;
         self.visited.clear();
+        if let Some(trace) = &mut self.trace {
+            *trace = Default::default();
+        }
     }
+
     fn run(&mut self) -> ProgramResult {
         loop {
-            if !self.visited.insert(self.ptr as usize) {
-                return ProgramResult::Looped(self.acc);
-            } else if self.ptr as usize == self.instructions.len() {
-                return ProgramResult::Terminated(self.acc);
+            if let Some(result) = self.step() {
+                return result;
             }
+        }
+    }
 
-            match self.instructions.get(self.ptr as usize) {
-                Some(Op::Acc(count)) => {
-                    self.acc += count;
-                    self.ptr += 1;
-                }
+    /// Executes the instruction at `ptr`,
+    /// or returns why the program has stopped without doing anything.
+    fn step(&mut self) -> Option<ProgramResult> {
+        if let Some(result) = self.halted() {
+            if let Some(trace) = &mut self.trace {
+                trace.end.get_or_insert((self.ptr, result));
+            }
+            return Some(result);
+        }
 
-                Some(Op::Jmp(count)) => {
-                    self.ptr += *count;
-                }
+        let (index, acc) = (self.ptr as usize, self.acc);
+        let op = self.instructions[index];
+        self.visited.insert(index);
+        match op {
+            Op::Acc(count) => {
+                self.acc += count;
+                self.ptr += 1;
+            }
+            Op::Jmp(count) => self.ptr += count,
+            Op::Nop(_) => self.ptr += 1,
+        }
 
-                Some(Op::Nop(_)) => self.ptr += 1,
+        if let Some(trace) = &mut self.trace {
+            trace.steps.push(trace::Step {
+                index,
+                op,
+                acc_before: acc,
+                acc_after: self.acc,
+            });
+        }
 
-                // We've know that ptr is > intstructions.len() due to if case above.
-                // So this must mean that ptr is not in [0..instructions.len()]
-                None => return ProgramResult::OutOfBounds,
-            }
+        None
+    }
+
+    /// Why the program stops before the instruction at `ptr`, if it does.
+    fn halted(&self) -> Option<ProgramResult> {
+        let len = self.instructions.len() as i64;
+        if self.ptr == len {
+            Some(ProgramResult::Terminated(self.acc))
+        } else if self.ptr < 0 || self.ptr > len {
+            Some(ProgramResult::OutOfBounds)
+        } else if self.visited.contains(&(self.ptr as usize)) {
+            Some(ProgramResult::Looped(self.acc))
+        } else {
+            None
         }
     }
+
+    /// The index and instruction at `ptr`, if it points at one.
+    fn current(&self) -> Option<(usize, Op)> {
+        let i = usize::try_from(self.ptr).ok()?;
+        self.instructions.get(i).map(|&op| (i, op))
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Op {
     Acc(i64),
     Jmp(i64),
     Nop(i64),
 }
+
+/// The kind of an [`Op`], without its argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Opcode {
+    Acc,
+    Jmp,
+    Nop,
+}
+
+/// The mnemonic, e.g. `jmp`.
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Acc => "acc",
+            Self::Jmp => "jmp",
+            Self::Nop => "nop",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Opcode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "acc" => Ok(Self::Acc),
+            "jmp" => Ok(Self::Jmp),
+            "nop" => Ok(Self::Nop),
+            s => Err(format!("Invalid Op `{}`", s)),
+        }
+    }
+}
+
 impl Op {
-    fn swap(&mut self) -> bool {
+    fn new(opcode: Opcode, arg: i64) -> Self {
+        match opcode {
+            Opcode::Acc => Self::Acc(arg),
+            Opcode::Jmp => Self::Jmp(arg),
+            Opcode::Nop => Self::Nop(arg),
+        }
+    }
+
+    fn arg(self) -> i64 {
         match self {
-            Self::Acc(_) => false,
-            Self::Jmp(n) => {
-                *self = Self::Nop(*n);
-                true
-            }
+            Self::Acc(n) | Self::Jmp(n) | Self::Nop(n) => n,
+        }
+    }
 
-            Self::Nop(n) => {
-                *self = Self::Jmp(*n);
-                true
-            }
+    fn opcode(self) -> Opcode {
+        match self {
+            Self::Acc(_) => Opcode::Acc,
+            Self::Jmp(_) => Opcode::Jmp,
+            Self::Nop(_) => Opcode::Nop,
+        }
+    }
+
+    /// `jmp` as `nop` and the other way around, `acc` can not be flipped.
+    fn flipped(self) -> Option<Self> {
+        match self {
+            Self::Acc(_) => None,
+            Self::Jmp(n) => Some(Self::Nop(n)),
+            Self::Nop(n) => Some(Self::Jmp(n)),
         }
     }
 }
 
+/// Parses a single instruction, e.g. `acc +1`, see [`asm::assemble`]
+/// for whole programs with comments and labels.
 impl FromStr for Op {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (op_s, count_s) = s.trim().split_at(3);
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let (op_s, count_s) = match words[..] {
+            [op_s, count_s] => (op_s, count_s),
+            _ => return Err(format!("Expected `<op> <count>`, found `{}`", s.trim())),
+        };
+
         let count = count_s
-            .trim()
             .parse::<i64>()
-            .map_err(|_| format!("Invalid count in Op `{}`", s))?;
+            .map_err(|_| format!("Invalid count in Op `{}`", s.trim()))?;
 
-        match op_s.trim() {
-            "acc" => Ok(Self::Acc(count)),
-            "jmp" => Ok(Self::Jmp(count)),
-            "nop" => Ok(Self::Nop(count)),
-            s => Err(format!("Invalid Op `{}`", s)),
+        Ok(Self::new(op_s.parse()?, count))
+    }
+}
+
+/// The canonical form, e.g. `acc +1` and `jmp -4`.
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:+}", self.opcode(), self.arg())
+    }
+}
+
+/// Finds the corrupted instruction in linear time.
+///
+/// Every instruction has a single successor, so the program is a graph
+/// and the instructions that end up terminating can be found by walking
+/// it backwards from the end. The fix is then the flip, on the path the
+/// program actually runs, that moves it onto one of those instructions.
+mod repair {
+    use super::Op;
+    use std::fmt;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Fix {
+        pub index: usize,
+        pub from: Op,
+        pub to: Op,
+        /// The accumulator when the repaired program terminates.
+        pub acc: i64,
+    }
+
+    impl fmt::Display for Fix {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                f,
+                "flipped `{}` at {} to `{}`, terminates with acc {}",
+                self.from, self.index, self.to, self.acc
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum RepairError {
+        /// The program terminates as it is, with this accumulator.
+        NotBroken(i64),
+        NoFix,
+        /// More than one flip makes the program terminate.
+        Ambiguous(Vec<Fix>),
+    }
+
+    impl fmt::Display for RepairError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Self::NotBroken(acc) => {
+                    write!(f, "the program already terminates, with acc {}", acc)
+                }
+                Self::NoFix => write!(f, "no single flip makes the program terminate"),
+                Self::Ambiguous(fixes) => write!(
+                    f,
+                    "{} flips make the program terminate: {}",
+                    fixes.len(),
+                    fixes
+                        .iter()
+                        .map(|fix| fix.to_string())
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for RepairError {}
+
+    /// The index run after `op` at `i`, if it is an instruction or the end.
+    /// A jump past the range of `i64` is out of bounds as well.
+    fn next(len: usize, i: usize, op: Op) -> Option<usize> {
+        let to = (i as i64).checked_add(match op {
+            Op::Jmp(n) => n,
+            Op::Acc(_) | Op::Nop(_) => 1,
+        })?;
+
+        if (0..=len as i64).contains(&to) {
+            Some(to as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The accumulator change of `op` when run.
+    fn acc_of(op: Op) -> i64 {
+        match op {
+            Op::Acc(n) => n,
+            Op::Jmp(_) | Op::Nop(_) => 0,
+        }
+    }
+
+    /// For the instructions that terminate when run from, and the end
+    /// itself at `ops.len()`, how much they add to the accumulator
+    /// on the way to the end. Instructions adding more than fits in
+    /// an `i64` are treated as never terminating.
+    pub fn reaches_end(ops: &[Op]) -> Vec<Option<i64>> {
+        let len = ops.len();
+        let mut prev = vec![Vec::new(); len + 1];
+        for (i, &op) in ops.iter().enumerate() {
+            if let Some(to) = next(len, i, op) {
+                prev[to].push(i);
+            }
+        }
+
+        let mut to_end = vec![None; len + 1];
+        to_end[len] = Some(0);
+        let mut stack = vec![len];
+        while let Some(i) = stack.pop() {
+            let acc = to_end[i].expect("only terminating instructions are pushed");
+            for &p in &prev[i] {
+                if to_end[p].is_none() {
+                    to_end[p] = acc_of(ops[p]).checked_add(acc);
+                    if to_end[p].is_some() {
+                        stack.push(p);
+                    }
+                }
+            }
+        }
+
+        to_end
+    }
+
+    pub fn repair(ops: &[Op]) -> Result<Fix, RepairError> {
+        let len = ops.len();
+        let to_end = reaches_end(ops);
+
+        // The instructions the program runs before looping or jumping away,
+        // and the accumulator before running each of them.
+        let mut path = Vec::new();
+        let mut seen = vec![false; len];
+        let mut acc = 0;
+        let mut ptr = Some(0);
+        while let Some(i) = ptr {
+            if i == len {
+                return Err(RepairError::NotBroken(acc));
+            }
+            if seen[i] {
+                break;
+            }
+
+            seen[i] = true;
+            path.push((i, acc));
+            acc = match acc.checked_add(acc_of(ops[i])) {
+                Some(acc) => acc,
+                None => break,
+            };
+            ptr = next(len, i, ops[i]);
+        }
+
+        // No instruction on `path` can reach the end, or the program would
+        // have terminated. So a flipped instruction is only ever run once
+        // and every flip landing on a terminating instruction is a fix,
+        // ending with the accumulator before it plus what follows the flip.
+        // Fixes overflowing the accumulator do not terminate either.
+        let fixes = path
+            .into_iter()
+            .filter_map(|(i, acc)| {
+                let to = ops[i].flipped()?;
+                let rest = next(len, i, to).and_then(|t| to_end[t])?;
+                Some(Fix {
+                    index: i,
+                    from: ops[i],
+                    to,
+                    acc: acc.checked_add(rest)?,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        match fixes.len() {
+            0 => Err(RepairError::NoFix),
+            1 => Ok(fixes[0]),
+            _ => Err(RepairError::Ambiguous(fixes)),
+        }
+    }
+}
+
+/// Reads and writes programs as text.
+///
+/// The assembler takes one instruction per line, with `#` or `;` starting
+/// a comment and blank lines ignored. A line may start with a label,
+/// `loop:`, which `jmp` and `nop` can use instead of an offset:
+///
+/// ```text
+/// loop: acc +1   # count up
+///       jmp loop
+/// ```
+mod asm {
+    use super::{Op, Opcode};
+    use std::{collections::HashMap, fmt};
+
+    /// A line that could not be assembled, `line` is 1-indexed.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct AsmError {
+        pub line: usize,
+        pub msg: String,
+    }
+
+    impl fmt::Display for AsmError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "line {}: {}", self.line, self.msg)
+        }
+    }
+
+    impl std::error::Error for AsmError {}
+
+    pub fn assemble(s: &str) -> Result<Vec<Op>, AsmError> {
+        // The line, opcode and argument of each instruction,
+        // with labels found in a first pass and resolved in a second.
+        let mut lines = Vec::new();
+        let mut labels = HashMap::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let err = |msg: String| AsmError { line: i + 1, msg };
+            let code = line.split(['#', ';']).next().unwrap_or("");
+
+            let code = match code.split_once(':') {
+                Some((label, rest)) => {
+                    let label = label.trim();
+                    if !is_label(label) {
+                        return Err(err(format!("Invalid label `{}`", label)));
+                    }
+                    if labels.insert(label, lines.len()).is_some() {
+                        return Err(err(format!("Label `{}` is defined twice", label)));
+                    }
+                    rest
+                }
+                None => code,
+            };
+
+            let words = code.split_whitespace().collect::<Vec<_>>();
+            match words[..] {
+                [] => (),
+                [op, arg] => {
+                    let opcode = op.parse::<Opcode>().map_err(err)?;
+                    lines.push((i + 1, opcode, arg));
+                }
+                _ => {
+                    return Err(err(format!(
+                        "Expected `<op> <count or label>`, found `{}`",
+                        code.trim()
+                    )))
+                }
+            }
+        }
+
+        lines
+            .iter()
+            .enumerate()
+            .map(|(index, &(line, opcode, arg))| {
+                let err = |msg: String| AsmError { line, msg };
+                let arg = match (arg.parse::<i64>(), opcode) {
+                    (Ok(n), _) => n,
+                    (Err(_), Opcode::Acc) if is_label(arg) => {
+                        return Err(err(format!("`acc` takes a count, found label `{}`", arg)))
+                    }
+                    (Err(_), _) if is_label(arg) => match labels.get(arg) {
+                        Some(&target) => target as i64 - index as i64,
+                        None => return Err(err(format!("Unknown label `{}`", arg))),
+                    },
+                    (Err(_), _) => return Err(err(format!("Invalid count `{}`", arg))),
+                };
+
+                Ok(Op::new(opcode, arg))
+            })
+            .collect()
+    }
+
+    fn is_label(s: &str) -> bool {
+        let mut chars = s.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    /// What [`disassemble`] adds, as a comment, after each instruction.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Listing {
+        /// The index of the instruction.
+        pub indices: bool,
+        /// The index `jmp` and `nop` would jump to.
+        pub targets: bool,
+    }
+
+    /// One canonical instruction per line, e.g. `jmp -4  # 7 -> 3`.
+    /// Assembles back to `ops`.
+    pub fn disassemble(ops: &[Op], listing: Listing) -> String {
+        let mut out = String::new();
+        for (i, op) in ops.iter().enumerate() {
+            let mut comment = Vec::new();
+            if listing.indices {
+                comment.push(i.to_string());
+            }
+            if let (true, Op::Jmp(n)) | (true, Op::Nop(n)) = (listing.targets, op) {
+                comment.push(format!("-> {}", i as i64 + n));
+            }
+
+            let line = match comment.len() {
+                0 => op.to_string(),
+                _ => format!("{:8} # {}", op.to_string(), comment.join(" ")),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Records every executed instruction, to look back at how a program
+/// ended up where it did.
+mod trace {
+    use super::{Op, Program, ProgramResult};
+    use std::fmt::Write;
+
+    /// One executed instruction.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Step {
+        pub index: usize,
+        pub op: Op,
+        pub acc_before: i64,
+        pub acc_after: i64,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct Trace {
+        pub steps: Vec<Step>,
+        /// Where and why the program halted, once it has.
+        pub end: Option<(i64, ProgramResult)>,
+    }
+
+    /// Runs `ops` until the program halts, recording a trace.
+    pub fn record(ops: &[Op]) -> Trace {
+        let mut program = ops.iter().copied().collect::<Program>();
+        program.trace = Some(Trace::default());
+        program.run();
+        program.trace.expect("trace was set")
+    }
+
+    impl Trace {
+        /// One line per step, `<step> [<index>] <op> <acc before> -> <acc after>`,
+        /// followed by how the program halted.
+        pub fn to_text(&self) -> String {
+            let mut out = String::new();
+            for (i, step) in self.steps.iter().enumerate() {
+                writeln!(
+                    out,
+                    "{:6} [{:4}] {:8} acc {} -> {}",
+                    i + 1,
+                    step.index,
+                    step.op.to_string(),
+                    step.acc_before,
+                    step.acc_after
+                )
+                .expect("writing trace");
+            }
+
+            if let Some((ptr, result)) = self.end {
+                writeln!(out, "halted at {}: {:?}", ptr, result).expect("writing trace");
+            }
+            out
+        }
+
+        pub fn to_json(&self) -> String {
+            let steps = self
+                .steps
+                .iter()
+                .map(|step| {
+                    format!(
+                        r#"{{"index":{},"op":"{}","arg":{},"acc_before":{},"acc_after":{}}}"#,
+                        step.index,
+                        step.op.opcode(),
+                        step.op.arg(),
+                        step.acc_before,
+                        step.acc_after
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let end = match self.end {
+                None => "null".to_string(),
+                Some((ptr, result)) => {
+                    let (kind, acc) = match result {
+                        ProgramResult::Looped(acc) => ("looped", acc.to_string()),
+                        ProgramResult::Terminated(acc) => ("terminated", acc.to_string()),
+                        ProgramResult::OutOfBounds => ("out_of_bounds", "null".to_string()),
+                    };
+                    format!(r#"{{"result":"{}","ptr":{},"acc":{}}}"#, kind, ptr, acc)
+                }
+            };
+
+            format!(r#"{{"steps":[{}],"end":{}}}"#, steps.join(","), end)
+        }
+
+        /// The pointer and accumulator as they were `back` steps before the end.
+        /// Returns `None` if the trace is not that long.
+        pub fn rewind(&self, back: usize) -> Option<(i64, i64)> {
+            let (end_ptr, _) = self.end?;
+            match back {
+                0 => Some((end_ptr, self.steps.last().map_or(0, |s| s.acc_after))),
+                n => {
+                    let step = self.steps.get(self.steps.len().checked_sub(n)?)?;
+                    Some((step.index as i64, step.acc_before))
+                }
+            }
+        }
+
+        /// Walks back from where the program halted, over the last `steps` steps:
+        /// "how did we get here".
+        pub fn explain_end(&self, steps: usize) -> String {
+            let mut out = match self.end {
+                Some((ptr, result)) => format!("halted at {}: {:?}\n", ptr, result),
+                None => "still running\n".to_string(),
+            };
+
+            for (n, step) in self.steps.iter().enumerate().rev().take(steps) {
+                writeln!(
+                    out,
+                    "  came from step {} at [{}] {}, acc {} -> {}",
+                    n + 1,
+                    step.index,
+                    step.op,
+                    step.acc_before,
+                    step.acc_after
+                )
+                .expect("writing trace");
+            }
+            out
+        }
+
+        /// Writes the steps next to each instruction of `ops`,
+        /// the way the puzzle text does: `acc +1  | 2, 8(!)`.
+        /// The `(!)` marks the step that would have run an instruction twice.
+        pub fn annotate(&self, ops: &[Op]) -> String {
+            let lines = ops.iter().map(|op| op.to_string()).collect::<Vec<_>>();
+            let mut visits = vec![Vec::new(); lines.len()];
+
+            for (n, step) in self.steps.iter().enumerate() {
+                visits[step.index].push((n + 1).to_string());
+            }
+            if let Some((ptr, ProgramResult::Looped(_))) = self.end {
+                visits[ptr as usize].push(format!("{}(!)", self.steps.len() + 1));
+            }
+
+            let width = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+            let mut out = String::new();
+            for (line, visits) in lines.iter().zip(visits) {
+                let annotated = format!("{:width$} | {}", line, visits.join(", "), width = width);
+                out.push_str(annotated.trim_end());
+                out.push('\n');
+            }
+            out
+        }
+    }
+}
+
+/// Starts the debugger on `path`, or on the puzzle input.
+pub fn debug(path: Option<&str>) -> crate::Result<()> {
+    debug::run(path)
+}
+
+/// Steps through a program, stopping at breakpoints and when watched
+/// conditions on `acc` become true.
+///
+/// Run with `cargo run debug --day 8 [program file]` and type `help`
+/// for the commands, which are modelled on gdb's.
+mod debug {
+    use super::{Opcode, Program, ProgramResult};
+    use std::{
+        fmt,
+        io::{self, BufRead, Write},
+        str::FromStr,
+    };
+
+    pub fn run(path: Option<&str>) -> crate::Result<()> {
+        let input = match path {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => crate::read_input("day08.txt")?,
+        };
+        let program = super::asm::assemble(&input)?
+            .into_iter()
+            .collect::<Program>();
+
+        let stdin = io::stdin();
+        repl(&mut Debugger::new(program), stdin.lock(), io::stdout())?;
+        Ok(())
+    }
+
+    /// Stops before executing the instruction it matches.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Breakpoint {
+        Index(usize),
+        Opcode(Opcode),
+    }
+
+    impl Breakpoint {
+        fn hits(self, program: &Program) -> bool {
+            match (self, program.current()) {
+                (Self::Index(i), Some((at, _))) => i == at,
+                (Self::Opcode(code), Some((_, op))) => code == op.opcode(),
+                (_, None) => false,
+            }
+        }
+    }
+
+    /// `break 4` or `break jmp`.
+    impl FromStr for Breakpoint {
+        type Err = String;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.parse::<usize>() {
+                Ok(i) => Ok(Self::Index(i)),
+                Err(_) => s
+                    .parse::<Opcode>()
+                    .map(Self::Opcode)
+                    .map_err(|_| format!("Expected an index or opcode, found `{}`", s)),
+            }
+        }
+    }
+
+    impl fmt::Display for Breakpoint {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Self::Index(i) => write!(f, "index {}", i),
+                Self::Opcode(code) => write!(f, "opcode {}", code),
+            }
+        }
+    }
+
+    /// Stops once `acc <cmp> value` becomes true.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Watch {
+        cmp: Cmp,
+        value: i64,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Cmp {
+        Eq,
+        Ne,
+        Lt,
+        Le,
+        Gt,
+        Ge,
+    }
+
+    impl Watch {
+        fn holds(self, acc: i64) -> bool {
+            match self.cmp {
+                Cmp::Eq => acc == self.value,
+                Cmp::Ne => acc != self.value,
+                Cmp::Lt => acc < self.value,
+                Cmp::Le => acc <= self.value,
+                Cmp::Gt => acc > self.value,
+                Cmp::Ge => acc >= self.value,
+            }
+        }
+    }
+
+    /// `acc > 10`, with any of `==`, `!=`, `<`, `<=`, `>` and `>=`.
+    impl FromStr for Watch {
+        type Err = String;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let words = s.split_whitespace().collect::<Vec<_>>();
+            let (cmp, value) = match words[..] {
+                ["acc", cmp, value] => (cmp, value),
+                _ => return Err(format!("Expected `acc <cmp> <value>`, found `{}`", s)),
+            };
+
+            let cmp = match cmp {
+                "==" => Cmp::Eq,
+                "!=" => Cmp::Ne,
+                "<" => Cmp::Lt,
+                "<=" => Cmp::Le,
+                ">" => Cmp::Gt,
+                ">=" => Cmp::Ge,
+                _ => return Err(format!("Unknown comparison `{}`", cmp)),
+            };
+            let value = value
+                .parse()
+                .map_err(|_| format!("Invalid value `{}`", value))?;
+
+            Ok(Self { cmp, value })
+        }
+    }
+
+    impl fmt::Display for Watch {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let cmp = match self.cmp {
+                Cmp::Eq => "==",
+                Cmp::Ne => "!=",
+                Cmp::Lt => "<",
+                Cmp::Le => "<=",
+                Cmp::Gt => ">",
+                Cmp::Ge => ">=",
+            };
+            write!(f, "acc {} {}", cmp, self.value)
+        }
+    }
+
+    /// Why [`Debugger::run_until`] returned.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Stop {
+        Breakpoint(Breakpoint),
+        Watch(Watch),
+        Halted(ProgramResult),
+    }
+
+    pub struct Debugger {
+        program: Program,
+        breakpoints: Vec<Breakpoint>,
+        watches: Vec<Watch>,
+    }
+
+    impl Debugger {
+        pub fn new(program: Program) -> Self {
+            Self {
+                program,
+                breakpoints: Vec::new(),
+                watches: Vec::new(),
+            }
+        }
+
+        /// Executes a single instruction, see [`Program::step`].
+        pub fn step(&mut self) -> Option<ProgramResult> {
+            self.program.step()
+        }
+
+        /// Runs until the program halts, a watch is triggered or the next
+        /// instruction hits `breakpoint` or any of the set breakpoints.
+        /// Always executes at least one instruction, unless halted.
+        pub fn run_until(&mut self, breakpoint: Option<Breakpoint>) -> Stop {
+            loop {
+                let acc = self.program.acc;
+                if let Some(result) = self.program.step() {
+                    return Stop::Halted(result);
+                }
+
+                let triggered = self
+                    .watches
+                    .iter()
+                    .find(|w| !w.holds(acc) && w.holds(self.program.acc));
+                if let Some(&watch) = triggered {
+                    return Stop::Watch(watch);
+                }
+
+                let program = &self.program;
+                let hit = self
+                    .breakpoints
+                    .iter()
+                    .chain(breakpoint.iter())
+                    .find(|bp| bp.hits(program));
+                if let Some(&bp) = hit {
+                    return Stop::Breakpoint(bp);
+                }
+            }
+        }
+
+        pub fn ptr(&self) -> i64 {
+            self.program.ptr
+        }
+
+        pub fn acc(&self) -> i64 {
+            self.program.acc
+        }
+
+        /// The executed instructions, in index order.
+        pub fn visited(&self) -> Vec<usize> {
+            let mut visited = self.program.visited.iter().copied().collect::<Vec<_>>();
+            visited.sort_unstable();
+            visited
+        }
+    }
+
+    const HELP: &str = "\
+step [n]          execute n instructions, s for short
+continue          run to the next breakpoint or watch, c for short
+until <bp>        continue, also stopping at the breakpoint <bp>
+break <bp>        stop before an index or opcode, e.g. `break 4` or `break jmp`
+watch acc <cmp> <n>  stop when the comparison becomes true, e.g. `watch acc > 3`
+delete            remove all breakpoints and watches
+info              list breakpoints and watches
+print ptr|acc|visited  show the state, p for short
+list              show the instructions around ptr, l for short
+reset             start over, keeping breakpoints and watches
+quit              leave the debugger, q for short";
+
+    /// Reads commands from `input` until it ends or `quit`.
+    pub fn repl(dbg: &mut Debugger, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{} instructions, type `help` for commands",
+            dbg.program.instructions.len()
+        )?;
+
+        for line in input.lines() {
+            let line = line?;
+            let (cmd, arg) = match line.trim().split_once(' ') {
+                Some((cmd, arg)) => (cmd, arg.trim()),
+                None => (line.trim(), ""),
+            };
+
+            match (cmd, arg) {
+                ("", _) => continue,
+                ("q", _) | ("quit", _) => break,
+                ("help", _) => writeln!(out, "{}", HELP)?,
+                ("s", n) | ("step", n) => {
+                    let count = if n.is_empty() {
+                        Ok(1)
+                    } else {
+                        n.parse::<usize>()
+                    };
+                    match count {
+                        Ok(n) => {
+                            for _ in 0..n {
+                                if let Some(result) = dbg.step() {
+                                    writeln!(out, "{}", halted(result))?;
+                                    break;
+                                }
+                            }
+                            where_(dbg, &mut out)?;
+                        }
+                        Err(_) => writeln!(out, "Invalid count `{}`", n)?,
+                    }
+                }
+                ("c", _) | ("continue", _) => {
+                    let stop = dbg.run_until(None);
+                    stopped(dbg, stop, &mut out)?;
+                }
+                ("until", bp) => match bp.parse::<Breakpoint>() {
+                    Ok(bp) => {
+                        let stop = dbg.run_until(Some(bp));
+                        stopped(dbg, stop, &mut out)?;
+                    }
+                    Err(err) => writeln!(out, "{}", err)?,
+                },
+                ("b", bp) | ("break", bp) => match bp.parse::<Breakpoint>() {
+                    Ok(bp) => {
+                        dbg.breakpoints.push(bp);
+                        writeln!(out, "Breakpoint {} at {}", dbg.breakpoints.len(), bp)?;
+                    }
+                    Err(err) => writeln!(out, "{}", err)?,
+                },
+                ("watch", w) => match w.parse::<Watch>() {
+                    Ok(w) => {
+                        dbg.watches.push(w);
+                        writeln!(out, "Watch {} on {}", dbg.watches.len(), w)?;
+                    }
+                    Err(err) => writeln!(out, "{}", err)?,
+                },
+                ("delete", _) => {
+                    dbg.breakpoints.clear();
+                    dbg.watches.clear();
+                    writeln!(out, "Deleted all breakpoints and watches")?;
+                }
+                ("info", _) => {
+                    for (i, bp) in dbg.breakpoints.iter().enumerate() {
+                        writeln!(out, "Breakpoint {} at {}", i + 1, bp)?;
+                    }
+                    for (i, w) in dbg.watches.iter().enumerate() {
+                        writeln!(out, "Watch {} on {}", i + 1, w)?;
+                    }
+                }
+                ("p", what) | ("print", what) => match what {
+                    "ptr" => writeln!(out, "ptr = {}", dbg.ptr())?,
+                    "acc" => writeln!(out, "acc = {}", dbg.acc())?,
+                    "visited" => writeln!(out, "visited = {:?}", dbg.visited())?,
+                    _ => writeln!(out, "Expected ptr, acc or visited, found `{}`", what)?,
+                },
+                ("l", _) | ("list", _) => {
+                    let ptr = dbg.ptr();
+                    for (i, op) in dbg.program.instructions.iter().enumerate() {
+                        if (i as i64 - ptr).abs() <= 3 {
+                            let marker = if i as i64 == ptr { "=>" } else { "  " };
+                            writeln!(out, "{} {:4}: {}", marker, i, op)?;
+                        }
+                    }
+                }
+                ("reset", _) => {
+                    dbg.program.reset();
+                    where_(dbg, &mut out)?;
+                }
+                _ => writeln!(out, "Unknown command `{}`, type `help` for commands", cmd)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn halted(result: ProgramResult) -> String {
+        match result {
+            ProgramResult::Looped(acc) => format!("Program looped, acc = {}", acc),
+            ProgramResult::Terminated(acc) => format!("Program terminated, acc = {}", acc),
+            ProgramResult::OutOfBounds => "Program jumped out of bounds".to_string(),
+        }
+    }
+
+    fn stopped(dbg: &Debugger, stop: Stop, out: &mut impl Write) -> io::Result<()> {
+        match stop {
+            Stop::Breakpoint(bp) => writeln!(out, "Breakpoint at {}", bp)?,
+            Stop::Watch(w) => writeln!(out, "Watch {} triggered", w)?,
+            Stop::Halted(result) => writeln!(out, "{}", halted(result))?,
+        }
+        where_(dbg, out)
+    }
+
+    /// Prints the next instruction and the accumulator.
+    fn where_(dbg: &Debugger, out: &mut impl Write) -> io::Result<()> {
+        match dbg.program.current() {
+            Some((i, op)) => writeln!(out, "=> {}: {}  acc = {}", i, op, dbg.acc()),
+            None => writeln!(out, "=> {}: -  acc = {}", dbg.ptr(), dbg.acc()),
         }
     }
 }
@@ -274,6 +1201,16 @@ impl FromStr for Op {
 #[cfg(test)]
 mod test {
 
+    static EXAMPLE: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
     #[test]
     fn part1_example() {
         let input = r#"
@@ -289,13 +1226,13 @@ acc +6
 "#
         .trim();
 
-        assert_eq!(5, super::part1(input));
+        assert_eq!(5, super::part1(input).expect("Running program"));
     }
 
     #[test]
     fn part1() {
         let input = crate::read_input("day08.txt").expect("reading input");
-        assert_eq!(1584, super::part1(&input));
+        assert_eq!(1584, super::part1(&input).expect("Running program"));
     }
 
     #[test]
@@ -313,12 +1250,245 @@ acc +6
 "#
         .trim();
 
-        assert_eq!(8, super::part2(input))
+        assert_eq!(8, super::part2(input).expect("Repairing program"))
+    }
+
+    #[test]
+    fn debugger() {
+        use super::debug::{self, Breakpoint, Stop};
+        use super::{Op, Opcode, Program, ProgramResult};
+
+        let program = |s: &str| {
+            s.lines()
+                .map(|line| line.parse::<Op>().expect("Parsing Op"))
+                .collect::<Program>()
+        };
+
+        let mut dbg = debug::Debugger::new(program(EXAMPLE));
+        assert_eq!(dbg.step(), None);
+        assert_eq!((dbg.ptr(), dbg.acc()), (1, 0));
+        assert_eq!(
+            dbg.run_until(Some(Breakpoint::Opcode(Opcode::Jmp))),
+            Stop::Breakpoint(Breakpoint::Opcode(Opcode::Jmp))
+        );
+        assert_eq!((dbg.ptr(), dbg.acc()), (2, 1));
+        assert_eq!(
+            dbg.run_until(Some(Breakpoint::Index(4))),
+            Stop::Breakpoint(Breakpoint::Index(4))
+        );
+        assert_eq!(dbg.visited(), [0, 1, 2, 3, 6, 7]);
+        assert_eq!(dbg.run_until(None), Stop::Halted(ProgramResult::Looped(5)));
+
+        let mut out = Vec::new();
+        let session = "break 6\nwatch acc > 1\nc\nc\np visited\nc\nfoo\nq\nstep\n";
+        debug::repl(
+            &mut debug::Debugger::new(program(EXAMPLE)),
+            session.as_bytes(),
+            &mut out,
+        )
+        .expect("running session");
+        assert_eq!(
+            String::from_utf8(out).expect("utf8"),
+            "9 instructions, type `help` for commands
+Breakpoint 1 at index 6
+Watch 1 on acc > 1
+Breakpoint at index 6
+=> 6: acc +1  acc = 1
+Watch acc > 1 triggered
+=> 7: jmp -4  acc = 2
+visited = [0, 1, 2, 6]
+Program looped, acc = 5
+=> 1: acc +1  acc = 5
+Unknown command `foo`, type `help` for commands
+"
+        );
+    }
+
+    #[test]
+    fn trace() {
+        use super::{trace, Op, ProgramResult};
+
+        let ops = super::asm::assemble(EXAMPLE).expect("assembling");
+        let trace = trace::record(&ops);
+        assert_eq!(trace.steps.len(), 7);
+        assert_eq!(
+            trace.steps[6],
+            trace::Step {
+                index: 4,
+                op: Op::Jmp(-3),
+                acc_before: 5,
+                acc_after: 5
+            }
+        );
+        assert_eq!(trace.end, Some((1, ProgramResult::Looped(5))));
+
+        assert_eq!(
+            trace.annotate(&ops),
+            "nop +0  | 1
+acc +1  | 2, 8(!)
+jmp +4  | 3
+acc +3  | 6
+jmp -3  | 7
+acc -99 |
+acc +1  | 4
+jmp -4  | 5
+acc +6  |
+"
+        );
+
+        assert_eq!(trace.rewind(0), Some((1, 5)));
+        assert_eq!(trace.rewind(1), Some((4, 5)));
+        assert_eq!(trace.rewind(7), Some((0, 0)));
+        assert_eq!(trace.rewind(8), None);
+
+        let json = trace.to_json();
+        assert!(json.starts_with(
+            r#"{"steps":[{"index":0,"op":"nop","arg":0,"acc_before":0,"acc_after":0},"#
+        ));
+        assert!(json.ends_with(r#"],"end":{"result":"looped","ptr":1,"acc":5}}"#));
+        assert_eq!(
+            trace.to_text().lines().nth(1),
+            Some("     2 [   1] acc +1   acc 0 -> 1")
+        );
+        assert_eq!(
+            trace.explain_end(1),
+            "halted at 1: Looped(5)\n  came from step 7 at [4] jmp -3, acc 5 -> 5\n"
+        );
+    }
+
+    #[test]
+    fn assembler() {
+        use super::asm::{assemble, disassemble, AsmError, Listing};
+        use super::Op;
+
+        let ops = assemble(
+            "# Counts to three
+start:  acc +1      ; one
+        nop end
+loop:   acc +2
+        jmp end
+
+end:    jmp loop",
+        )
+        .expect("assembling");
+        assert_eq!(
+            ops,
+            [Op::Acc(1), Op::Nop(3), Op::Acc(2), Op::Jmp(1), Op::Jmp(-2)]
+        );
+
+        let plain = disassemble(&ops, Listing::default());
+        assert_eq!(plain, "acc +1\nnop +3\nacc +2\njmp +1\njmp -2\n");
+        let full = disassemble(
+            &ops,
+            Listing {
+                indices: true,
+                targets: true,
+            },
+        );
+        assert_eq!(full.lines().nth(4), Some("jmp -2   # 4 -> 2"));
+        assert_eq!(full.lines().next(), Some("acc +1   # 0"));
+
+        for text in &[plain, full] {
+            assert_eq!(assemble(text).as_ref(), Ok(&ops));
+        }
+        assert_eq!(
+            disassemble(&assemble(EXAMPLE).expect("assembling"), Listing::default()),
+            format!("{}\n", EXAMPLE)
+        );
+
+        let err = |s| assemble(s).expect_err(s);
+        let at = |line, msg: &str| AsmError {
+            line,
+            msg: msg.to_string(),
+        };
+        assert_eq!(
+            err("nop +0\njmp"),
+            at(2, "Expected `<op> <count or label>`, found `jmp`")
+        );
+        assert_eq!(
+            err("ac"),
+            at(1, "Expected `<op> <count or label>`, found `ac`")
+        );
+        assert_eq!(err("mul +2"), at(1, "Invalid Op `mul`"));
+        assert_eq!(err("jmp nowhere"), at(1, "Unknown label `nowhere`"));
+        assert_eq!(
+            err("a: nop +0\na: nop +0"),
+            at(2, "Label `a` is defined twice")
+        );
+        assert_eq!(
+            err("a: acc a"),
+            at(1, "`acc` takes a count, found label `a`")
+        );
+        assert_eq!(err("jmp 1x"), at(1, "Invalid count `1x`"));
+        assert_eq!(err("1a: nop +0"), at(1, "Invalid label `1a`"));
+
+        assert!("jm".parse::<Op>().is_err());
+        assert_eq!("  jmp   -4 ".parse::<Op>(), Ok(Op::Jmp(-4)));
+        assert_eq!(Op::Nop(0).to_string(), "nop +0");
+    }
+
+    #[test]
+    fn repair() {
+        use super::asm::assemble;
+        use super::repair::{reaches_end, repair, Fix, RepairError};
+        use super::Op;
+
+        let ops = assemble(EXAMPLE).expect("assembling");
+        assert_eq!(
+            reaches_end(&ops),
+            [
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(6),
+                Some(0)
+            ]
+        );
+        assert_eq!(
+            repair(&ops),
+            Ok(Fix {
+                index: 7,
+                from: Op::Jmp(-4),
+                to: Op::Nop(-4),
+                acc: 8
+            })
+        );
+
+        let repair = |s| repair(&assemble(s).expect("assembling"));
+        assert_eq!(repair("acc +1\nnop +5"), Err(RepairError::NotBroken(1)));
+        assert_eq!(repair("acc +1\njmp -1\njmp -1"), Err(RepairError::NoFix));
+        assert_eq!(
+            repair("jmp +3\nacc +1").map(|fix| fix.index),
+            Ok(0),
+            "jumping out of bounds is fixed too"
+        );
+        assert_eq!(
+            repair("nop +0\njmp +9223372036854775807").map(|fix| fix.index),
+            Ok(1),
+            "jumping past i64::MAX is out of bounds"
+        );
+
+        let overflowing = assemble("acc +9223372036854775807\nacc +1").expect("assembling");
+        assert_eq!(reaches_end(&overflowing), [None, Some(1), Some(0)]);
+        assert_eq!(super::repair::repair(&overflowing), Err(RepairError::NoFix));
+
+        let err = repair("nop +2\njmp -1\nacc +1").expect_err("ambiguous");
+        assert_eq!(
+            err.to_string(),
+            "2 flips make the program terminate: \
+             flipped `nop +2` at 0 to `jmp +2`, terminates with acc 1; \
+             flipped `jmp -1` at 1 to `nop -1`, terminates with acc 1"
+        );
     }
 
     #[test]
     fn part2() {
         let input = crate::read_input("day08.txt").expect("Reading input");
-        assert_eq!(920, super::part2(&input))
+        assert_eq!(920, super::part2(&input).expect("Repairing program"))
     }
 }
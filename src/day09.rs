@@ -119,10 +119,7 @@ pub fn solve() -> crate::Result<()> {
 
 /// Find the first invalid numer according to the XMAS protocol.
 fn part1(preamble_size: usize, s: &str) -> u64 {
-    verify(preamble_size, s)
-        .err()
-        .expect("Wanted error")
-        .element
+    verify(preamble_size, s).expect_err("Wanted error")
 }
 
 /// Find contiguous set of numbers in `s` that sums to target.
@@ -161,7 +158,9 @@ fn part2(target: u64, s: &str) -> u64 {
     min + max
 }
 
-fn verify(preamble_size: usize, s: &str) -> Result<(), XmasDecodeError> {
+/// Returns the first number that is not the sum of two of the
+/// `preamble_size` numbers before it.
+fn verify(preamble_size: usize, s: &str) -> Result<(), u64> {
     let mut it = s
         .trim()
         .lines()
@@ -175,13 +174,13 @@ fn verify(preamble_size: usize, s: &str) -> Result<(), XmasDecodeError> {
     }
 
     // Verify remaining
-    for (index, element) in it.enumerate() {
+    for element in it {
         if !preamble
             .iter()
             .enumerate()
             .any(|(i, n)| preamble.iter().skip(i).any(|n2| n + n2 == element))
         {
-            return Err(XmasDecodeError { index, element });
+            return Err(element);
         }
 
         preamble.remove(0);
@@ -191,11 +190,6 @@ fn verify(preamble_size: usize, s: &str) -> Result<(), XmasDecodeError> {
     Ok(())
 }
 
-#[derive(Debug, Clone, Copy)]
-struct XmasDecodeError {
-    index: usize,
-    element: u64,
-}
 #[cfg(test)]
 mod tests {
 
@@ -225,7 +219,7 @@ mod tests {
 "#
         .trim();
 
-        assert_eq!(127, super::part1(5, &input));
+        assert_eq!(127, super::part1(5, input));
     }
 
     #[test]
@@ -259,7 +253,7 @@ mod tests {
 576
 "#
         .trim();
-        assert_eq!(62, super::part2(127, &input));
+        assert_eq!(62, super::part2(127, input));
     }
 
     #[test]
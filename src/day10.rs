@@ -129,7 +129,7 @@ pub fn solve() -> crate::Result<()> {
 }
 
 fn part1(s: &str) -> i64 {
-    let ns = read_and_sort(&s);
+    let ns = read_and_sort(s);
 
     let mut diff1 = 0;
     let mut diff3 = 1; // Built in device always + 3 jolts higher.
@@ -179,7 +179,7 @@ mod test {
 12
 4
 "#;
-        assert_eq!(7 * 5, super::part1(&s));
+        assert_eq!(7 * 5, super::part1(s));
     }
 
     #[test]
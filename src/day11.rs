@@ -24,7 +24,7 @@ impl Board {
     fn from_str(s: &str) -> Self {
         let s = s.trim();
         Self {
-            width: s.find('\n').unwrap_or_else(|| s.len()),
+            width: s.find('\n').unwrap_or(s.len()),
             seats: s
                 .chars()
                 .filter(|c| *c != '\n')
@@ -135,7 +135,7 @@ impl fmt::Display for Board {
         let board = self
             .seats
             .chunks(self.width)
-            .map(|line| line.iter().map(Seat::to_char).collect::<String>())
+            .map(|line| line.iter().map(|seat| seat.to_char()).collect::<String>())
             .collect::<Vec<_>>()
             .join("\n");
 
@@ -210,7 +210,7 @@ impl Seat {
         }
     }
 
-    fn to_char(&self) -> char {
+    fn to_char(self) -> char {
         match self {
             Self::Empty => 'L',
             Self::Occupied => '#',
@@ -162,13 +162,13 @@ enum Direction {
     West = 3,
 }
 
-impl Into<Op> for Direction {
-    fn into(self) -> Op {
-        match self {
-            Self::North => Op::MoveNorth,
-            Self::East => Op::MoveEast,
-            Self::South => Op::MoveSouth,
-            Self::West => Op::MoveWest,
+impl From<Direction> for Op {
+    fn from(dir: Direction) -> Self {
+        match dir {
+            Direction::North => Op::MoveNorth,
+            Direction::East => Op::MoveEast,
+            Direction::South => Op::MoveSouth,
+            Direction::West => Op::MoveWest,
         }
     }
 }
@@ -191,7 +191,7 @@ impl Direction {
         let disc = self as usize;
         let new_deg = (disc + deg / 90) % 4;
 
-        Self::from(new_deg as usize)
+        Self::from(new_deg)
     }
 
     fn rotate_right(self, deg: usize) -> Self {
@@ -25,6 +25,10 @@ pub fn read_input(input_file: &str) -> Result<String> {
 }
 
 fn main() -> Result<()> {
+    if std::env::args().nth(1).as_deref() == Some("debug") {
+        return debug(std::env::args().skip(2));
+    }
+
     let days = &[
         day01::solve,
         day02::solve,
@@ -55,3 +59,21 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// `cargo run debug --day <day> [args]` starts the debugger of a day.
+fn debug(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut day = None;
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--day" => day = args.next(),
+            _ => rest.push(arg),
+        }
+    }
+
+    match day.as_deref() {
+        Some("8") => day08::debug(rest.first().map(String::as_str)),
+        Some(day) => Err(format!("There is no debugger for day {}", day).into()),
+        None => Err("Missing `--day <day>`".into()),
+    }
+}